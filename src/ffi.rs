@@ -169,4 +169,375 @@ unsafe extern "system" {
     ) -> c_int;
     
     pub fn wdi_get_wdf_version() -> c_int;
-}
\ No newline at end of file
+}
+
+// SetupAPI types, used to query device properties Windows holds outside libwdi
+pub type HDevInfo = *mut std::ffi::c_void;
+pub type HKey = *mut std::ffi::c_void;
+
+pub const DIGCF_PRESENT: DWORD = 0x0000_0002;
+pub const DIGCF_DEVICEINTERFACE: DWORD = 0x0000_0010;
+pub const DICS_FLAG_GLOBAL: DWORD = 0x0000_0001;
+pub const DIREG_DEV: DWORD = 0x0000_0001;
+pub const KEY_READ: DWORD = 0x0002_0019;
+pub const REG_SZ: DWORD = 1;
+pub const REG_BINARY: DWORD = 3;
+pub type ScHandle = *mut std::ffi::c_void;
+
+pub const SC_MANAGER_CONNECT: DWORD = 0x0001;
+pub const SERVICE_QUERY_STATUS: DWORD = 0x0004;
+pub const SERVICE_START: DWORD = 0x0010;
+pub const SERVICE_STOP: DWORD = 0x0020;
+pub const SERVICE_CONTROL_STOP: DWORD = 1;
+pub const SERVICE_STOPPED: DWORD = 1;
+
+pub const WAIT_OBJECT_0: DWORD = 0;
+
+pub const ERROR_SERVICE_ALREADY_RUNNING: i32 = 1056;
+pub const ERROR_SERVICE_NOT_ACTIVE: i32 = 1062;
+
+#[repr(C)]
+#[derive(Default)]
+pub struct ServiceStatus {
+    pub service_type: DWORD,
+    pub current_state: DWORD,
+    pub controls_accepted: DWORD,
+    pub win32_exit_code: DWORD,
+    pub service_specific_exit_code: DWORD,
+    pub check_point: DWORD,
+    pub wait_hint: DWORD,
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct SystemTime {
+    pub year: u16,
+    pub month: u16,
+    pub day_of_week: u16,
+    pub day: u16,
+    pub hour: u16,
+    pub minute: u16,
+    pub second: u16,
+    pub milliseconds: u16,
+}
+
+// Sign-extended, as in the Windows headers
+pub const HKEY_LOCAL_MACHINE: HKey = 0x8000_0002_u32 as i32 as isize as HKey;
+
+pub const DEVPROP_TYPE_UINT32: u32 = 0x0000_0007;
+pub const DEVPROP_TYPE_GUID: u32 = 0x0000_000D;
+pub const DEVPROP_TYPE_FILETIME: u32 = 0x0000_0010;
+pub const DEVPROP_TYPE_STRING: u32 = 0x0000_0012;
+
+pub const DN_STARTED: u32 = 0x0000_0008;
+pub const DN_HAS_PROBLEM: u32 = 0x0000_0400;
+
+pub const CM_PROB_NOT_CONFIGURED: u32 = 1;
+pub const CM_PROB_REINSTALL: u32 = 18;
+pub const CM_PROB_FAILED_INSTALL: u32 = 28;
+
+pub const SUOI_FORCEDELETE: DWORD = 0x0000_0001;
+
+pub const ERROR_FILE_NOT_FOUND: i32 = 2;
+pub const ERROR_ACCESS_DENIED: i32 = 5;
+pub const ERROR_INF_IN_USE_BY_DEVICES: i32 = 0xE000_023D_u32 as i32;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Guid {
+    pub data1: u32,
+    pub data2: u16,
+    pub data3: u16,
+    pub data4: [u8; 8],
+}
+
+#[repr(C)]
+pub struct DevPropKey {
+    pub fmtid: Guid,
+    pub pid: u32,
+}
+
+#[repr(C)]
+pub struct SpDevinfoData {
+    pub cb_size: DWORD,
+    pub class_guid: Guid,
+    pub dev_inst: DWORD,
+    pub reserved: usize,
+}
+
+// The size of SP_DEVICE_INTERFACE_DETAIL_DATA_W's fixed part, which must be
+// set in its cbSize field - it is packed on 32-bit Windows.
+#[cfg(target_pointer_width = "64")]
+pub const SP_DEVICE_INTERFACE_DETAIL_DATA_SIZE: DWORD = 8;
+#[cfg(not(target_pointer_width = "64"))]
+pub const SP_DEVICE_INTERFACE_DETAIL_DATA_SIZE: DWORD = 6;
+
+#[repr(C)]
+pub struct SpDeviceInterfaceData {
+    pub cb_size: DWORD,
+    pub interface_class_guid: Guid,
+    pub flags: DWORD,
+    pub reserved: usize,
+}
+
+// {a45c254e-df1c-4efd-8020-67d146a850e0}, 13
+pub const DEVPKEY_DEVICE_MANUFACTURER: DevPropKey = DevPropKey {
+    fmtid: Guid {
+        data1: 0xa45c254e,
+        data2: 0xdf1c,
+        data3: 0x4efd,
+        data4: [0x80, 0x20, 0x67, 0xd1, 0x46, 0xa8, 0x50, 0xe0],
+    },
+    pid: 13,
+};
+
+// {a45c254e-df1c-4efd-8020-67d146a850e0}, 14
+pub const DEVPKEY_DEVICE_FRIENDLY_NAME: DevPropKey = DevPropKey {
+    fmtid: DEVPKEY_DEVICE_MANUFACTURER.fmtid,
+    pid: 14,
+};
+
+// {4340a6c5-93fa-4706-972c-7b648008a5a7}, 8
+pub const DEVPKEY_DEVICE_PARENT: DevPropKey = DevPropKey {
+    fmtid: Guid {
+        data1: 0x4340a6c5,
+        data2: 0x93fa,
+        data3: 0x4706,
+        data4: [0x97, 0x2c, 0x7b, 0x64, 0x80, 0x08, 0xa5, 0xa7],
+    },
+    pid: 8,
+};
+
+// {4340a6c5-93fa-4706-972c-7b648008a5a7}, 2
+pub const DEVPKEY_DEVICE_DEV_NODE_STATUS: DevPropKey = DevPropKey {
+    fmtid: DEVPKEY_DEVICE_PARENT.fmtid,
+    pid: 2,
+};
+
+// {4340a6c5-93fa-4706-972c-7b648008a5a7}, 3
+pub const DEVPKEY_DEVICE_PROBLEM_CODE: DevPropKey = DevPropKey {
+    fmtid: DEVPKEY_DEVICE_PARENT.fmtid,
+    pid: 3,
+};
+
+// {a45c254e-df1c-4efd-8020-67d146a850e0}, 15
+pub const DEVPKEY_DEVICE_LOCATION_INFO: DevPropKey = DevPropKey {
+    fmtid: DEVPKEY_DEVICE_MANUFACTURER.fmtid,
+    pid: 15,
+};
+
+// {a45c254e-df1c-4efd-8020-67d146a850e0}, 6
+pub const DEVPKEY_DEVICE_SERVICE: DevPropKey = DevPropKey {
+    fmtid: DEVPKEY_DEVICE_MANUFACTURER.fmtid,
+    pid: 6,
+};
+
+// {a8b865dd-2e3d-4094-ad97-e593a70c75d6}, 3
+pub const DEVPKEY_DEVICE_DRIVER_VERSION: DevPropKey = DevPropKey {
+    fmtid: Guid {
+        data1: 0xa8b865dd,
+        data2: 0x2e3d,
+        data3: 0x4094,
+        data4: [0xad, 0x97, 0xe5, 0x93, 0xa7, 0x0c, 0x75, 0xd6],
+    },
+    pid: 3,
+};
+
+// {a8b865dd-2e3d-4094-ad97-e593a70c75d6}, 5
+pub const DEVPKEY_DEVICE_DRIVER_INF_PATH: DevPropKey = DevPropKey {
+    fmtid: DEVPKEY_DEVICE_DRIVER_VERSION.fmtid,
+    pid: 5,
+};
+
+// {a8b865dd-2e3d-4094-ad97-e593a70c75d6}, 2
+pub const DEVPKEY_DEVICE_DRIVER_DATE: DevPropKey = DevPropKey {
+    fmtid: DEVPKEY_DEVICE_DRIVER_VERSION.fmtid,
+    pid: 2,
+};
+
+// {a8b865dd-2e3d-4094-ad97-e593a70c75d6}, 9
+pub const DEVPKEY_DEVICE_DRIVER_PROVIDER: DevPropKey = DevPropKey {
+    fmtid: DEVPKEY_DEVICE_DRIVER_VERSION.fmtid,
+    pid: 9,
+};
+
+// {8c7ed206-3f8a-4827-b3ab-ae9e1faefc6c}, 2
+pub const DEVPKEY_DEVICE_CONTAINER_ID: DevPropKey = DevPropKey {
+    fmtid: Guid {
+        data1: 0x8c7ed206,
+        data2: 0x3f8a,
+        data3: 0x4827,
+        data4: [0xb3, 0xab, 0xae, 0x9e, 0x1f, 0xae, 0xfc, 0x6c],
+    },
+    pid: 2,
+};
+
+#[link(name = "setupapi")]
+unsafe extern "system" {
+    pub fn SetupDiGetClassDevsW(
+        class_guid: *const Guid,
+        enumerator: *const u16,
+        hwnd: HWND,
+        flags: DWORD,
+    ) -> HDevInfo;
+
+    pub fn SetupDiEnumDeviceInterfaces(
+        device_info_set: HDevInfo,
+        device_info_data: *const SpDevinfoData,
+        interface_class_guid: *const Guid,
+        member_index: DWORD,
+        device_interface_data: *mut SpDeviceInterfaceData,
+    ) -> BOOL;
+
+    pub fn SetupDiGetDeviceInterfaceDetailW(
+        device_info_set: HDevInfo,
+        device_interface_data: *const SpDeviceInterfaceData,
+        device_interface_detail_data: *mut u8,
+        device_interface_detail_data_size: DWORD,
+        required_size: *mut DWORD,
+        device_info_data: *mut SpDevinfoData,
+    ) -> BOOL;
+
+    pub fn SetupDiCreateDeviceInfoList(class_guid: *const Guid, hwnd: HWND) -> HDevInfo;
+
+    pub fn SetupDiOpenDeviceInfoW(
+        device_info_set: HDevInfo,
+        device_instance_id: *const u16,
+        hwnd: HWND,
+        open_flags: DWORD,
+        device_info_data: *mut SpDevinfoData,
+    ) -> BOOL;
+
+    pub fn SetupDiGetDevicePropertyW(
+        device_info_set: HDevInfo,
+        device_info_data: *const SpDevinfoData,
+        property_key: *const DevPropKey,
+        property_type: *mut u32,
+        property_buffer: *mut u8,
+        property_buffer_size: DWORD,
+        required_size: *mut DWORD,
+        flags: DWORD,
+    ) -> BOOL;
+
+    pub fn SetupDiDestroyDeviceInfoList(device_info_set: HDevInfo) -> BOOL;
+
+    pub fn SetupDiOpenDevRegKey(
+        device_info_set: HDevInfo,
+        device_info_data: *const SpDevinfoData,
+        scope: DWORD,
+        hw_profile: DWORD,
+        key_type: DWORD,
+        sam_desired: DWORD,
+    ) -> HKey;
+
+    pub fn CMP_WaitNoPendingInstallEvents(timeout: DWORD) -> DWORD;
+
+    pub fn SetupUninstallOEMInfW(
+        inf_file_name: *const u16,
+        flags: DWORD,
+        reserved: *mut std::ffi::c_void,
+    ) -> BOOL;
+
+    pub fn SetupGetInfDriverStoreLocationW(
+        file_name: *const u16,
+        alternate_platform_info: *const std::ffi::c_void,
+        locale_name: *const u16,
+        return_buffer: *mut u16,
+        return_buffer_size: DWORD,
+        required_size: *mut DWORD,
+    ) -> BOOL;
+}
+
+#[link(name = "advapi32")]
+unsafe extern "system" {
+    pub fn OpenSCManagerW(machine_name: *const u16, database_name: *const u16, desired_access: DWORD) -> ScHandle;
+
+    pub fn OpenServiceW(sc_manager: ScHandle, service_name: *const u16, desired_access: DWORD) -> ScHandle;
+
+    pub fn ControlService(service: ScHandle, control: DWORD, status: *mut ServiceStatus) -> BOOL;
+
+    pub fn QueryServiceStatus(service: ScHandle, status: *mut ServiceStatus) -> BOOL;
+
+    pub fn StartServiceW(service: ScHandle, num_args: DWORD, args: *const *const u16) -> BOOL;
+
+    pub fn CloseServiceHandle(handle: ScHandle) -> BOOL;
+
+    pub fn RegOpenKeyExW(
+        key: HKey,
+        sub_key: *const u16,
+        options: DWORD,
+        sam_desired: DWORD,
+        result: *mut HKey,
+    ) -> c_int;
+
+    pub fn RegQueryValueExW(
+        key: HKey,
+        value_name: *const u16,
+        reserved: *mut DWORD,
+        value_type: *mut DWORD,
+        data: *mut u8,
+        data_len: *mut DWORD,
+    ) -> c_int;
+
+    pub fn RegCloseKey(key: HKey) -> c_int;
+}
+
+#[link(name = "kernel32")]
+unsafe extern "system" {
+    pub fn LocaleNameToLCID(name: *const u16, flags: DWORD) -> DWORD;
+
+    pub fn GetThreadUILanguage() -> u16;
+
+    pub fn SetThreadUILanguage(lang_id: u16) -> u16;
+
+    pub fn FileTimeToSystemTime(file_time: *const u64, system_time: *mut SystemTime) -> BOOL;
+}
+
+// WinTrust types, used to verify catalog signatures
+pub const WTD_UI_NONE: DWORD = 2;
+pub const WTD_REVOKE_NONE: DWORD = 0;
+pub const WTD_CHOICE_FILE: DWORD = 1;
+pub const WTD_STATEACTION_VERIFY: DWORD = 1;
+pub const WTD_STATEACTION_CLOSE: DWORD = 2;
+
+pub const TRUST_E_NOSIGNATURE: i32 = 0x800B_0100_u32 as i32;
+pub const TRUST_E_SUBJECT_FORM_UNKNOWN: i32 = 0x800B_0003_u32 as i32;
+pub const TRUST_E_PROVIDER_UNKNOWN: i32 = 0x800B_0001_u32 as i32;
+
+// {00AAC56B-CD44-11d0-8CC2-00C04FC295EE}
+pub const WINTRUST_ACTION_GENERIC_VERIFY_V2: Guid = Guid {
+    data1: 0x00aa_c56b,
+    data2: 0xcd44,
+    data3: 0x11d0,
+    data4: [0x8c, 0xc2, 0x00, 0xc0, 0x4f, 0xc2, 0x95, 0xee],
+};
+
+#[repr(C)]
+pub struct WintrustFileInfo {
+    pub cb_struct: DWORD,
+    pub file_path: *const u16,
+    pub file: *mut std::ffi::c_void,
+    pub known_subject: *const Guid,
+}
+
+#[repr(C)]
+pub struct WintrustData {
+    pub cb_struct: DWORD,
+    pub policy_callback_data: *mut std::ffi::c_void,
+    pub sip_client_data: *mut std::ffi::c_void,
+    pub ui_choice: DWORD,
+    pub revocation_checks: DWORD,
+    pub union_choice: DWORD,
+    pub file: *mut WintrustFileInfo,
+    pub state_action: DWORD,
+    pub state_data: *mut std::ffi::c_void,
+    pub url_reference: *mut u16,
+    pub prov_flags: DWORD,
+    pub ui_context: DWORD,
+    pub signature_settings: *mut std::ffi::c_void,
+}
+
+#[link(name = "wintrust")]
+unsafe extern "system" {
+    pub fn WinVerifyTrust(hwnd: HWND, action_id: *const Guid, data: *mut WintrustData) -> i32;
+}
@@ -0,0 +1,190 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Programmatic generation of WinUSB INF files.
+
+use crate::{DeviceInterfaceGuid, Error};
+use crate::ring_log::error;
+use std::fmt::Write;
+
+/// Parameters for [`generate_winusb_inf`]
+#[derive(Debug, Clone)]
+pub struct WinUsbInfParams {
+    /// USB Vendor ID
+    pub vid: u16,
+    /// USB Product ID
+    pub pid: u16,
+    /// Interface number, for installing on one interface of a composite device
+    pub mi: Option<u8>,
+    /// Device description, as shown in Device Manager
+    pub device_description: String,
+    /// Manufacturer name, as shown in Device Manager
+    pub manufacturer: String,
+    /// Driver provider name
+    pub provider: String,
+    /// Device interface GUID applications use to find the device
+    pub device_guid: DeviceInterfaceGuid,
+}
+
+/// Generates a minimal, complete WinUSB INF file.
+///
+/// The result can be passed straight to
+/// [`DriverInstaller::with_inf_data`](crate::DriverInstaller::with_inf_data),
+/// giving full control over the INF contents without authoring one by hand.
+/// As with the crate's sample INF, the catalog filename and driver date and
+/// version are left as libwdi placeholders, to be filled in when the driver
+/// is prepared.
+///
+/// # Errors
+/// * Returns `Error::InvalidParam` if any of the strings are empty or contain
+///   control characters.  The GUID format is validated when parsing the
+///   [`DeviceInterfaceGuid`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use wdi_rs::{generate_winusb_inf, DriverInstaller, WinUsbInfParams};
+///
+/// let inf = generate_winusb_inf(WinUsbInfParams {
+///     vid: 0x1234,
+///     pid: 0x5678,
+///     mi: None,
+///     device_description: "ACME Widget".to_string(),
+///     manufacturer: "ACME Corp".to_string(),
+///     provider: "ACME Corp".to_string(),
+///     device_guid: "{6e45736a-2b1b-4078-b772-b3af2b6fde1c}".parse()?,
+/// })?;
+///
+/// DriverInstaller::for_device(0x1234, 0x5678)
+///     .with_inf_data(inf.as_bytes(), "acme_widget.inf")
+///     .install()?;
+/// # Ok::<(), wdi_rs::Error>(())
+/// ```
+pub fn generate_winusb_inf(params: WinUsbInfParams) -> Result<String, Error> {
+    let strings = [
+        ("device_description", &params.device_description),
+        ("manufacturer", &params.manufacturer),
+        ("provider", &params.provider),
+    ];
+    for (name, value) in strings {
+        if value.trim().is_empty() || value.chars().any(char::is_control) {
+            error!("Invalid INF {}: {:?}", name, value);
+            return Err(Error::InvalidParam);
+        }
+    }
+
+    let mut hardware_id = format!("USB\\VID_{:04X}&PID_{:04X}", params.vid, params.pid);
+    if let Some(mi) = params.mi {
+        write!(hardware_id, "&MI_{:02X}", mi).unwrap();
+    }
+
+    // LF line endings, as libwdi requires
+    let mut inf = String::new();
+    inf.push_str("; Generated by wdi-rs\n\n");
+    inf.push_str(concat!(
+        "[Version]\n",
+        "Signature   = \"$Windows NT$\"\n",
+        "Class       = \"USBDevice\"\n",
+        "ClassGuid   = {88bae032-5a81-49f0-bc3d-a4ff138216d6}\n",
+        "Provider    = %ProviderName%\n",
+        "CatalogFile = #CAT_FILENAME#\n",
+        "DriverVer   = #DRIVER_DATE#, #DRIVER_VERSION#\n\n",
+        "[ClassInstall32]\n",
+        "AddReg = WinUSBDeviceClassReg\n\n",
+        "[WinUSBDeviceClassReg]\n",
+        "HKR,,,0,\"Universal Serial Bus devices\"\n",
+        "HKR,,Icon,,-20\n\n",
+        "[Manufacturer]\n",
+        "%ManufacturerName% = Standard,NTx86,NTamd64,NTarm64\n\n",
+    ));
+    for arch in ["NTx86", "NTamd64", "NTarm64"] {
+        write!(inf, "[Standard.{}]\n%DeviceName% = USB_Install, {}\n\n", arch, hardware_id).unwrap();
+    }
+    inf.push_str(concat!(
+        "[USB_Install]\n",
+        "Include = winusb.inf\n",
+        "Needs   = WINUSB.NT\n\n",
+        "[USB_Install.Services]\n",
+        "Include = winusb.inf\n",
+        "Needs   = WINUSB.NT.Services\n\n",
+        "[USB_Install.HW]\n",
+        "AddReg = Dev_AddReg\n\n",
+        "[Dev_AddReg]\n",
+        "HKR,,DeviceInterfaceGUIDs,0x10000,%DeviceGUID%\n\n",
+    ));
+    write!(
+        inf,
+        "[Strings]\nManufacturerName = {}\nProviderName     = {}\nDeviceName       = {}\nDeviceGUID       = \"{}\"\n",
+        inf_quote(&params.manufacturer),
+        inf_quote(&params.provider),
+        inf_quote(&params.device_description),
+        params.device_guid,
+    ).unwrap();
+
+    Ok(inf)
+}
+
+/// Quotes a value for an INF `[Strings]` section, escaping `"` and `%`.
+///
+/// Control characters, which would end the line, are replaced with spaces.
+pub(crate) fn inf_quote(value: &str) -> String {
+    let value: String = value.chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    format!("\"{}\"", value.replace('"', "\"\"").replace('%', "%%"))
+}
+
+/// Whether a value must be escaped with [`inf_quote`] to be used in an INF.
+pub(crate) fn needs_inf_escaping(value: &str) -> bool {
+    value.chars().any(|c| c == '"' || c == '%' || c.is_control())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> WinUsbInfParams {
+        WinUsbInfParams {
+            vid: 0x1234,
+            pid: 0xabcd,
+            mi: Some(2),
+            device_description: "50% \"Widget\"".to_string(),
+            manufacturer: "ACME".to_string(),
+            provider: "ACME".to_string(),
+            device_guid: "{6e45736a-2b1b-4078-b772-b3af2b6fde1c}".parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_generate_winusb_inf() {
+        let inf = generate_winusb_inf(params()).unwrap();
+        assert!(inf.contains("[Standard.NTamd64]\n%DeviceName% = USB_Install, USB\\VID_1234&PID_ABCD&MI_02\n"));
+        assert!(inf.contains("DeviceName       = \"50%% \"\"Widget\"\"\"\n"));
+        assert!(inf.contains("DeviceGUID       = \"{6E45736A-2B1B-4078-B772-B3AF2B6FDE1C}\"\n"));
+        assert!(!inf.contains('\r'));
+    }
+
+    #[test]
+    fn test_inf_quote() {
+        assert_eq!(inf_quote("Widget"), "\"Widget\"");
+        assert_eq!(inf_quote("My \"Special\" 100% Device"), "\"My \"\"Special\"\" 100%% Device\"");
+        assert_eq!(inf_quote("Line\r\nbreak\0"), "\"Line  break \"");
+
+        assert!(!needs_inf_escaping("Widget (COM3) & more"));
+        assert!(needs_inf_escaping("100%"));
+        assert!(needs_inf_escaping("\"Widget\""));
+        assert!(needs_inf_escaping("Tab\there"));
+    }
+
+    #[test]
+    fn test_generate_winusb_inf_invalid() {
+        let mut bad = params();
+        bad.manufacturer = " ".to_string();
+        assert!(generate_winusb_inf(bad).is_err());
+
+        let mut bad = params();
+        bad.device_description = "Line\nbreak".to_string();
+        assert!(generate_winusb_inf(bad).is_err());
+    }
+}
@@ -63,22 +63,36 @@
 
 use std::fmt;
 use std::fs;
-use std::path::PathBuf;
-use log::{debug, error, info, trace, warn};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::task::Poll;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use crate::ring_log::{debug, error, info, trace, warn};
 use tempfile::TempDir;
 
 // Import the low-level wdi types
 use crate::{
-    create_list, prepare_driver, install_driver, 
+    create_list, prepare_driver, install_driver, is_driver_supported,
     CreateListOptions, PrepareDriverOptions, InstallDriverOptions,
-    Device, DriverType, Error as WdiError,
+    Device, DeviceInterfaceGuid, DriverKind, DriverType, Error as WdiError,
 };
+use crate::inf::{inf_quote, needs_inf_escaping};
+use crate::wdi::{get_vendor_name, serial_from_instance_id};
+use crate::ffi::{DEVPKEY_DEVICE_SERVICE, DEVPKEY_DEVICE_PROBLEM_CODE, DEVPKEY_DEVICE_DEV_NODE_STATUS};
+use crate::ffi::{DN_STARTED, DN_HAS_PROBLEM, CM_PROB_NOT_CONFIGURED, CM_PROB_REINSTALL, CM_PROB_FAILED_INSTALL};
+use crate::setupapi::{driver_store_inf, interface_registered, DeviceInfo};
+use crate::system::{is_reboot_pending, ThreadUiLanguage};
+use crate::wintrust::{verify_signature, SignatureStatus};
 
 /// Strategy for selecting which USB device to install a driver for.
 pub enum DeviceSelector {
     /// Select a device by USB Vendor ID and Product ID.
     ///
     /// If multiple devices match, the first one found will be used.
+    /// For a composite device, that is its first enumerated interface - use
+    /// [`VidPidMi`](DeviceSelector::VidPidMi) to select a specific one.
     VidPid { 
         /// USB Vendor ID
         vid: u16, 
@@ -89,14 +103,83 @@ pub enum DeviceSelector {
     /// Select the first device matching a predicate function.
     ///
     /// The predicate receives a reference to each device and returns `true`
-    /// if it should be selected.
-    First(Box<dyn Fn(&Device) -> bool>),
+    /// if it should be selected.  It must be `Send`, so the installer can
+    /// move to another thread - it is always called on the thread which
+    /// starts the installation, including for
+    /// [`DriverInstaller::install_spawn`].
+    First(Box<dyn Fn(&Device) -> bool + Send>),
     
     /// Use a specific device that was previously enumerated.
     ///
     /// This is useful when you've already called [`create_list`] and want
     /// to install a driver for a specific device from that list.
     Specific(Device),
+    
+    /// Select the first device matching any of the given (VID, PID) pairs.
+    ///
+    /// Useful for a family of products, with several PIDs under one VID or
+    /// multiple VIDs.
+    AnyVidPid(Vec<(u16, u16)>),
+    
+    /// Select the device at the given position in the enumeration.
+    ///
+    /// Devices are enumerated with `list_all` set, so the index matches
+    /// [`DeviceList::enumerate`](crate::DeviceList::enumerate) on a list
+    /// created with `list_all: true`.  This is useful for CLI tools which
+    /// print a numbered device list for the user to choose from.
+    Index(usize),
+    
+    /// Select every device matching a predicate function, for use with
+    /// [`DriverInstaller::install_all`].
+    ///
+    /// This is useful when several identical devices are connected at once.
+    /// With the single device install methods, the first matching device is
+    /// used.
+    All(Box<dyn Fn(&Device) -> bool + Send>),
+    
+    /// Select a device by USB Vendor ID, Product ID and serial number.
+    ///
+    /// This targets a specific unit when several identical devices are
+    /// connected.  A device matches if the serial number in its `device_id`
+    /// equals `serial`, compared case-insensitively - Windows embeds the
+    /// serial number as the last component of a USB device's instance ID.
+    /// For an interface of a composite device, whose instance ID doesn't
+    /// include the serial number, the parent device's serial number is
+    /// checked instead - see [`Device::serial_number`].
+    Serial {
+        /// USB Vendor ID
+        vid: u16,
+        /// USB Product ID
+        pid: u16,
+        /// Serial number
+        serial: String,
+    },
+    
+    /// Select the first device whose name, as shown in Device Manager,
+    /// contains the given string, compared case-insensitively.
+    ///
+    /// This lets users copy the name straight from Device Manager.  The name
+    /// is the device's [friendly name](Device::friendly_name), or for devices
+    /// without one, its description.
+    FriendlyName(String),
+    
+    /// Select one interface of a composite device by USB Vendor ID, Product
+    /// ID and interface number.
+    ///
+    /// Windows binds a driver to each interface of a composite device
+    /// separately, and [`VidPid`](DeviceSelector::VidPid) matches whichever
+    /// interface is enumerated first.  Use this for multi-function devices
+    /// where only one interface should get the driver, for example WinUSB
+    /// for a vendor interface alongside a CDC serial port.  Only composite
+    /// devices match.
+    VidPidMi {
+        /// USB Vendor ID
+        vid: u16,
+        /// USB Product ID
+        pid: u16,
+        /// Interface number, as reported in [`Device::mi`]
+        mi: u8,
+    },
 }
 
 impl fmt::Debug for DeviceSelector {
@@ -105,6 +188,83 @@ impl fmt::Debug for DeviceSelector {
             Self::VidPid { vid, pid } => write!(f, "VidPid({:04x}:{:04x})", vid, pid),
             Self::First(_) => write!(f, "First(<predicate>)"),
             Self::Specific(dev) => write!(f, "Specific({})", dev),
+            Self::AnyVidPid(pairs) => {
+                let pairs: Vec<_> = pairs.iter()
+                    .map(|(vid, pid)| format!("{:04x}:{:04x}", vid, pid))
+                    .collect();
+                write!(f, "AnyVidPid({})", pairs.join(", "))
+            }
+            Self::Index(index) => write!(f, "Index({})", index),
+            Self::All(_) => write!(f, "All(<predicate>)"),
+            Self::Serial { vid, pid, serial } => write!(f, "Serial({:04x}:{:04x}, {})", vid, pid, serial),
+            Self::FriendlyName(name) => write!(f, "FriendlyName({})", name),
+            Self::VidPidMi { vid, pid, mi } => write!(f, "VidPidMi({:04x}:{:04x}, {})", vid, pid, mi),
+        }
+    }
+}
+
+/// Identifies a device for batch operations, such as
+/// [`install_targets_typed`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceTarget {
+    /// The first device with the given USB Vendor ID and Product ID.
+    VidPid {
+        /// USB Vendor ID
+        vid: u16,
+        /// USB Product ID
+        pid: u16,
+    },
+    
+    /// The device with the given device instance ID, as reported in
+    /// [`Device::device_id`].  Compared case-insensitively.
+    DeviceId(String),
+    
+    /// The device with the given USB Vendor ID and Product ID, optionally
+    /// restricted to one interface of a composite device and/or a serial
+    /// number.
+    Usb {
+        /// USB Vendor ID
+        vid: u16,
+        /// USB Product ID
+        pid: u16,
+        /// Interface number, for an interface of a composite device
+        mi: Option<u8>,
+        /// Serial number, as returned by [`Device::serial_number`]
+        serial: Option<String>,
+    },
+}
+
+impl DeviceTarget {
+    /// Returns `true` if the device is identified by this target.
+    pub fn matches(&self, device: &Device) -> bool {
+        match self {
+            Self::VidPid { vid, pid } => device.vid == *vid && device.pid == *pid,
+            Self::DeviceId(id) => device.device_id.as_deref()
+                .is_some_and(|device_id| device_id.eq_ignore_ascii_case(id)),
+            Self::Usb { vid, pid, mi, serial } => {
+                device.vid == *vid && device.pid == *pid
+                    && mi.is_none_or(|mi| device.is_composite && device.mi == mi)
+                    && serial.as_ref().is_none_or(|serial| device.serial_number().as_ref() == Some(serial))
+            }
+        }
+    }
+}
+
+impl fmt::Display for DeviceTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::VidPid { vid, pid } => write!(f, "{:04x}:{:04x}", vid, pid),
+            Self::DeviceId(id) => write!(f, "{}", id),
+            Self::Usb { vid, pid, mi, serial } => {
+                write!(f, "{:04x}:{:04x}", vid, pid)?;
+                if let Some(mi) = mi {
+                    write!(f, " interface {}", mi)?;
+                }
+                if let Some(serial) = serial {
+                    write!(f, " serial {}", serial)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -133,6 +293,37 @@ pub enum InfSource {
     /// This is the default and simplest option if you don't need
     /// custom INF file contents.
     Generated,
+    
+    /// Use the INF file in a directory which matches the device.
+    ///
+    /// The directory's INF files are searched for the device's hardware ID
+    /// at installation time, and any matching INF is then used as for
+    /// [`External`](InfSource::External).  Where several match, one is
+    /// chosen according to the [`InfSelection`].
+    Directory {
+        /// Path to the directory containing the INF files
+        path: PathBuf,
+        /// How to choose between several matching INF files
+        selection: InfSelection,
+    },
+}
+
+/// How to choose between several INF files matching a device - see
+/// [`DriverInstaller::with_inf_directory`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InfSelection {
+    /// Fail with [`Error::InvalidParam`](crate::Error::InvalidParam) if more
+    /// than one INF matches.
+    #[default]
+    Error,
+    /// Use the first matching INF, by filename.
+    FirstMatch,
+    /// Use the INF with the highest `DriverVer` version, falling back to the
+    /// newest date for equal versions.
+    NewestVersion,
+    /// Use the INF with the newest `DriverVer` date, falling back to the
+    /// highest version for equal dates.
+    Newest,
 }
 
 impl fmt::Debug for InfSource {
@@ -144,6 +335,8 @@ impl fmt::Debug for InfSource {
                 write!(f, "External({})", path.display()),
             Self::Generated => 
                 write!(f, "Generated"),
+            Self::Directory { path, selection } => 
+                write!(f, "Directory({}, {:?})", path.display(), selection),
         }
     }
 }
@@ -154,6 +347,44 @@ impl Default for InfSource {
     }
 }
 
+/// The INF for one installation, once any [`InfSource::Directory`] has been
+/// resolved to the file matching the device.
+enum PackageInf {
+    Embedded { data: Vec<u8>, filename: String },
+    External(PathBuf),
+    Generated,
+}
+
+impl PackageInf {
+    /// The INF's filename within the driver directory.
+    fn name(&self) -> String {
+        match self {
+            Self::Embedded { filename, .. } => filename.clone(),
+            Self::External(path) => path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            Self::Generated => "generated.inf".to_string(),
+        }
+    }
+}
+
+/// The INF and prepare options for installing on one device, resolved from
+/// the builder's settings - see [`DriverInstaller::resolve_package`].
+struct ResolvedPackage {
+    inf: PackageInf,
+    prepare_opts: PrepareDriverOptions,
+}
+
+/// A package staged on disk, ready for libwdi to prepare - see
+/// [`DriverInstaller::stage_package`].
+struct StagedPackage {
+    driver_path: String,
+    inf_path: String,
+    /// Removes the staged files when dropped, unless they were prepared in
+    /// place
+    temp_dir: Option<TempDir>,
+}
+
 /// Options for driver installation.
 ///
 /// Wraps the low-level [`PrepareDriverOptions`] and [`InstallDriverOptions`]
@@ -196,11 +427,58 @@ impl Default for InstallOptions {
 /// ```
 pub struct DriverInstaller {
     device_selector: DeviceSelector,
-    driver_type: DriverType,
+    driver_type: Option<DriverType>,
+    vendor_name: Option<String>,
+    device_description: Option<String>,
     inf_source: InfSource,
     options: InstallOptions,
+    verify_present: bool,
+    best_effort: bool,
+    ui_language: Option<String>,
+    interface_registration: Option<(DeviceInterfaceGuid, Duration)>,
+    ready_timeout: Option<Duration>,
+    chooser: Option<Chooser>,
+    on_installed: Option<InstalledCallback>,
+    progress: Option<ProgressCallback>,
+    temp_prefix: Option<String>,
+    temp_dir: Option<PathBuf>,
+    keep_files: bool,
+    vendor_lookup: bool,
+    force_replace: bool,
+    only_if_unknown: bool,
+    coinstaller: Option<((u16, u16), Vec<u8>)>,
+    backup_dir: Option<PathBuf>,
+    retry: Option<(u32, Duration)>,
+    additional_files: Vec<(PathBuf, Vec<u8>)>,
+    device_class: Option<(String, DeviceInterfaceGuid)>,
+    device_guid: Option<DeviceInterfaceGuid>,
+    wcid: Option<bool>,
+    replug: Option<(Duration, ReplugPrompt)>,
+    install_mode: InstallMode,
 }
 
+/// Chooses between multiple matching devices - see
+/// [`DriverInstaller::with_chooser`].
+type Chooser = Box<dyn Fn(&[DeviceChoice]) -> Option<usize> + Send>;
+
+/// Called with the installed device - see [`DriverInstaller::on_installed`].
+type InstalledCallback = Box<dyn Fn(&Device) + Send>;
+
+/// Called at each phase of an installation - see
+/// [`DriverInstaller::with_progress`].
+type ProgressCallback = Box<dyn Fn(InstallPhase) + Send>;
+
+/// Asks the user to replug the installed device - see
+/// [`DriverInstaller::verify_across_replug`].
+type ReplugPrompt = Box<dyn Fn(&Device) + Send>;
+
+/// Each device installed, with its result - see
+/// [`DriverInstaller::install_all`].
+pub type DeviceResults = Vec<(Device, Result<(), WdiError>)>;
+
+/// Each target with its installation result - see [`install_targets_with`].
+type TargetResults = Vec<(DeviceTarget, Result<Device, WdiError>)>;
+
 impl DriverInstaller {
     /// Create a new installer with a custom device selector.
     ///
@@ -225,9 +503,34 @@ impl DriverInstaller {
         debug!("Creating DriverInstaller with selector: {:?}", device_selector);
         Self {
             device_selector,
-            driver_type: DriverType::WinUsb,
+            driver_type: None,
+            vendor_name: None,
+            device_description: None,
             inf_source: InfSource::default(),
             options: InstallOptions::default(),
+            verify_present: true,
+            best_effort: false,
+            ui_language: None,
+            interface_registration: None,
+            ready_timeout: None,
+            chooser: None,
+            on_installed: None,
+            progress: None,
+            temp_prefix: None,
+            temp_dir: None,
+            keep_files: false,
+            vendor_lookup: false,
+            force_replace: false,
+            only_if_unknown: false,
+            coinstaller: None,
+            backup_dir: None,
+            retry: None,
+            additional_files: Vec::new(),
+            device_class: None,
+            device_guid: None,
+            wcid: None,
+            replug: None,
+            install_mode: InstallMode::default(),
         }
     }
     
@@ -266,6 +569,93 @@ impl DriverInstaller {
         Self::new(DeviceSelector::Specific(device))
     }
     
+    /// Create an installer for the first device matching any of the given
+    /// (VID, PID) pairs.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let installer = DriverInstaller::for_any_vid_pid(vec![(0x1234, 0x5678), (0x1234, 0x5679)]);
+    /// ```
+    pub fn for_any_vid_pid(pairs: Vec<(u16, u16)>) -> Self {
+        info!("Creating installer for any of {} VID:PID pairs", pairs.len());
+        Self::new(DeviceSelector::AnyVidPid(pairs))
+    }
+    
+    /// Create an installer for the device at the given position in the
+    /// enumeration.
+    ///
+    /// See [`DeviceSelector::Index`] for how devices are enumerated.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let installer = DriverInstaller::for_index(3);
+    /// ```
+    pub fn for_index(index: usize) -> Self {
+        info!("Creating installer for device index {}", index);
+        Self::new(DeviceSelector::Index(index))
+    }
+    
+    /// Create an installer for the device with the given VID, PID and serial
+    /// number.
+    ///
+    /// See [`DeviceSelector::Serial`] for how the serial number is matched.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let installer = DriverInstaller::for_serial(0x1234, 0x5678, "ABC123");
+    /// ```
+    pub fn for_serial(vid: u16, pid: u16, serial: impl Into<String>) -> Self {
+        let serial = serial.into();
+        info!("Creating installer for VID:PID {:04x}:{:04x} serial {}", vid, pid, serial);
+        Self::new(DeviceSelector::Serial { vid, pid, serial })
+    }
+    
+    /// Create an installer for one interface of a composite device.
+    ///
+    /// See [`DeviceSelector::VidPidMi`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// // Install WinUSB on interface 2 only, leaving the others alone
+    /// let device = DriverInstaller::for_interface(0x1234, 0x5678, 2)
+    ///     .install()?;
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn for_interface(vid: u16, pid: u16, mi: u8) -> Self {
+        info!("Creating installer for VID:PID {:04x}:{:04x} interface {}", vid, pid, mi);
+        Self::new(DeviceSelector::VidPidMi { vid, pid, mi })
+    }
+    
+    /// Create an installer for the first device whose Device Manager name
+    /// contains `name`.
+    ///
+    /// See [`DeviceSelector::FriendlyName`] for how the name is matched.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let installer = DriverInstaller::for_friendly_name("ACME Widget (COM3)");
+    /// ```
+    pub fn for_friendly_name(name: impl Into<String>) -> Self {
+        let name = name.into();
+        info!("Creating installer for device named {}", name);
+        Self::new(DeviceSelector::FriendlyName(name))
+    }
+    
     /// Set the INF source to embedded data.
     ///
     /// The provided data will be written to a temporary file during installation.
@@ -309,9 +699,184 @@ impl DriverInstaller {
         self
     }
     
+    /// Set the INF source to a directory of INF files, using the one which
+    /// matches the device.
+    ///
+    /// This suits driver folders holding INFs for several devices, or which
+    /// accumulate several versions of a driver over time.  If more than one
+    /// INF matches the device, installation fails unless a different
+    /// strategy is set with [`inf_selection`](DriverInstaller::inf_selection).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::{DriverInstaller, InfSelection};
+    /// use std::path::PathBuf;
+    ///
+    /// let installer = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_inf_directory(PathBuf::from("C:\\drivers"))
+    ///     .inf_selection(InfSelection::NewestVersion);
+    /// ```
+    pub fn with_inf_directory(mut self, path: PathBuf) -> Self {
+        debug!("Setting INF source to directory: {}", path.display());
+        let selection = match &self.inf_source {
+            InfSource::Directory { selection, .. } => *selection,
+            _ => InfSelection::default(),
+        };
+        self.inf_source = InfSource::Directory { path, selection };
+        self
+    }
+    
+    /// Set how to choose between several INF files matching the device, when
+    /// using [`with_inf_directory`](DriverInstaller::with_inf_directory).
+    ///
+    /// Has no effect for other INF sources.
+    ///
+    /// Defaults to [`InfSelection::Error`].
+    pub fn inf_selection(mut self, strategy: InfSelection) -> Self {
+        debug!("Setting INF selection to: {:?}", strategy);
+        if let InfSource::Directory { selection, .. } = &mut self.inf_source {
+            *selection = strategy;
+        } else {
+            warn!("INF selection only applies to an INF directory - ignoring");
+        }
+        self
+    }
+    
+    /// Add files to stage alongside the INF, such as driver binaries or a
+    /// catalog referenced by an embedded INF.
+    ///
+    /// Each file is given as a path relative to the driver directory, e.g.
+    /// `amd64/widget.sys`, and its contents.  This can be called more than
+    /// once to add more files.  Files are only staged for
+    /// [`InfSource::Embedded`] and [`InfSource::Generated`] - an external
+    /// INF's files should already be alongside it.
+    ///
+    /// Before anything is staged, installation fails with
+    /// [`Error::InvalidParam`](crate::Error::InvalidParam) if two files, or
+    /// a file and the INF, would be staged to the same path.  Paths are
+    /// compared case-insensitively, as Windows filesystems are, and must be
+    /// relative without `..` components.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let sys = std::fs::read("widget.sys").unwrap();
+    /// let installer = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_inf_data(include_bytes!("..\\inf\\sample.inf"), "widget.inf")
+    ///     .with_additional_files(vec![("amd64/widget.sys".into(), sys)]);
+    /// ```
+    pub fn with_additional_files(mut self, files: Vec<(PathBuf, Vec<u8>)>) -> Self {
+        debug!("Adding {} additional file(s) to stage", files.len());
+        self.additional_files.extend(files);
+        self
+    }
+    
+    /// Place the device in a specific setup class, rather than the default
+    /// for the driver type.
+    ///
+    /// The `Class` and `ClassGuid` entries in the INF's `[Version]` section
+    /// are replaced with `name` and `guid` before the driver is staged, so
+    /// the device appears under that class in Device Manager.  This is for
+    /// advanced scenarios, such as custom drivers which must be in a bespoke
+    /// class.  It applies to generated INFs and embedded templates - with an
+    /// external INF, set the class in the file itself.
+    ///
+    /// Installation fails with [`Error::InvalidParam`](crate::Error::InvalidParam)
+    /// if `name` is empty or contains characters which aren't valid in an
+    /// INF value, if the INF has no `[Version]` section, or if an external
+    /// INF is used.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let guid = "{6e45736a-2b1b-4078-b772-b3af2b6fde1c}".parse()?;
+    /// DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_device_class("AcmeWidgets", guid)
+    ///     .install()?;
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn with_device_class(mut self, name: &str, guid: DeviceInterfaceGuid) -> Self {
+        debug!("Setting device class to: {} {}", name, guid);
+        self.device_class = Some((name.to_string(), guid));
+        self
+    }
+    
+    /// Set the WinUSB device interface GUID registered for the device.
+    ///
+    /// User-mode applications find the device by this GUID, for example with
+    /// `SetupDiGetClassDevs`, so set a stable GUID of your own rather than
+    /// relying on the one libwdi generates.  Parse it from a string with
+    /// `str::parse`, which fails with
+    /// [`Error::InvalidParam`](crate::Error::InvalidParam) if it isn't of the
+    /// form `{xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx}`.
+    ///
+    /// This sets [`PrepareDriverOptions::device_guid`], taking precedence
+    /// over any options passed to
+    /// [`with_prepare_options`](DriverInstaller::with_prepare_options).  It
+    /// only applies to generated INFs - an embedded or external INF
+    /// registers its own GUID.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let guid = "{6e45736a-2b1b-4078-b772-b3af2b6fde1c}".parse()?;
+    /// DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_device_guid(guid)
+    ///     .ensure_interface_registered(guid, Duration::from_secs(10))
+    ///     .install()?;
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn with_device_guid(mut self, guid: DeviceInterfaceGuid) -> Self {
+        debug!("Setting device interface GUID to: {}", guid);
+        self.device_guid = Some(guid);
+        self
+    }
+    
+    /// Set whether to install the generic WCID driver, for devices which
+    /// provide Microsoft OS descriptors.
+    ///
+    /// WCID devices tell Windows which driver they need, so the generated
+    /// INF matches the device by its compatible ID rather than its VID and
+    /// PID - the cleanest route for such devices.  This sets
+    /// [`PrepareDriverOptions::use_wcid_driver`], taking precedence over any
+    /// options passed to
+    /// [`with_prepare_options`](DriverInstaller::with_prepare_options).
+    ///
+    /// WCID installs pair with the default [`InfSource::Generated`].  An
+    /// embedded or external INF decides for itself which devices it matches,
+    /// so may conflict - a warning is logged if one is used.  A warning is
+    /// also logged if Windows reports the device doesn't advertise a
+    /// Microsoft OS descriptor - see [`Device::supports_wcid`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_wcid(true)
+    ///     .install()?;
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn with_wcid(mut self, yes: bool) -> Self {
+        debug!("Setting WCID driver to: {}", yes);
+        self.wcid = Some(yes);
+        self
+    }
+    
     /// Set the driver type to install.
     ///
-    /// Defaults to [`DriverType::WinUsb`] if not specified.
+    /// Takes precedence over the `driver_type` in any options passed to
+    /// [`with_prepare_options`](DriverInstaller::with_prepare_options).  If
+    /// neither is specified, defaults to [`DriverType::WinUsb`].
     ///
     /// # Examples
     ///
@@ -323,15 +888,95 @@ impl DriverInstaller {
     /// ```
     pub fn with_driver_type(mut self, driver_type: DriverType) -> Self {
         debug!("Setting driver type to: {:?}", driver_type);
-        self.driver_type = driver_type;
+        self.driver_type = Some(driver_type);
+        self
+    }
+    
+    /// Set the vendor name to use for the driver.
+    ///
+    /// Takes precedence over the `vendor_name` in any options passed to
+    /// [`with_prepare_options`](DriverInstaller::with_prepare_options).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let installer = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_vendor_name("ACME Corp");
+    /// ```
+    pub fn with_vendor_name(mut self, vendor_name: impl Into<String>) -> Self {
+        let vendor_name = vendor_name.into();
+        debug!("Setting vendor name to: {}", vendor_name);
+        self.vendor_name = Some(vendor_name);
+        self
+    }
+    
+    /// Set whether to look up the vendor name from the device's USB Vendor
+    /// ID when none has been set.
+    ///
+    /// The name is taken from libwdi's database of USB vendors, giving a more
+    /// professional-looking generated INF than libwdi's generic name.  The
+    /// name is looked up for each device installed, so installers covering
+    /// several devices use each device's own vendor.
+    ///
+    /// Defaults to `false`, for libwdi's default behavior.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let installer = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_vendor_lookup(true);
+    /// ```
+    pub fn with_vendor_lookup(mut self, lookup: bool) -> Self {
+        debug!("Setting vendor lookup to: {}", lookup);
+        self.vendor_lookup = lookup;
+        self
+    }
+    
+    /// Set the device description shown in Device Manager.
+    ///
+    /// Only used with [`InfSource::Generated`], where it replaces the
+    /// description libwdi writes into the generated INF's `[Strings]` section.
+    /// Custom INF files should set their own strings.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let installer = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_vendor_name("ACME Corp")
+    ///     .with_device_description("ACME Widget");
+    /// ```
+    pub fn with_device_description(mut self, description: impl Into<String>) -> Self {
+        let description = description.into();
+        debug!("Setting device description to: {}", description);
+        self.device_description = Some(description);
         self
     }
     
     /// Set custom options for the driver preparation phase.
     ///
-    /// Note: The `external_inf` field will be automatically set based on
-    /// the [`InfSource`] and any value you set will be overridden. A warning
-    /// will be logged if you attempt to set it.
+    /// The options are merged with the other builder settings at install time,
+    /// regardless of the order the builder methods were called in.  From
+    /// highest to lowest precedence:
+    /// 1. `external_inf` is always set based on the [`InfSource`], and any
+    ///    value you set will be overridden. A warning will be logged if you
+    ///    attempt to set it.
+    /// 2. Explicit builder calls - [`with_driver_type`] and
+    ///    [`with_vendor_name`] - override the corresponding fields.
+    /// 3. All other fields are used as provided here.
+    ///
+    /// [`with_driver_type`]: DriverInstaller::with_driver_type
+    /// [`with_vendor_name`]: DriverInstaller::with_vendor_name
+    ///
+    /// Setting both `disable_cat` and `disable_signing` installs the driver
+    /// unsigned, for development on machines with test signing enabled.
+    /// Missing catalog and signature errors while preparing the driver are
+    /// then logged and ignored, rather than failing the installation.
     ///
     /// # Examples
     ///
@@ -369,280 +1014,3072 @@ impl DriverInstaller {
         self
     }
     
-    /// Perform the driver installation.
+    /// Set whether to check the device is still connected immediately before
+    /// installing.
     ///
-    /// This will:
-    /// 1. Find the target device (if not already specified)
-    /// 2. Check if a driver is already installed
-    /// 3. Prepare the driver files
-    /// 4. Install the driver
+    /// Devices can be unplugged between being selected and the driver being
+    /// installed, which otherwise causes a confusing mid-install failure.  With
+    /// this enabled, [`install`](DriverInstaller::install) re-enumerates
+    /// devices first and returns [`Error::NoDevice`](crate::Error::NoDevice) if
+    /// the device has gone.
     ///
-    /// Returns the [`Device`] for the device that was installed.
+    /// Defaults to `true`.
     ///
-    /// # Errors
+    /// # Examples
     ///
-    /// Returns an error if:
-    /// - The device cannot be found
-    /// - A non-WinUSB driver is already installed
-    /// - Driver preparation fails
-    /// - Driver installation fails
-    /// - File I/O operations fail
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let installer = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .verify_present_before_install(false);
+    /// ```
+    pub fn verify_present_before_install(mut self, verify: bool) -> Self {
+        debug!("Setting verify present before install to: {}", verify);
+        self.verify_present = verify;
+        self
+    }
+    
+    /// Wait, after installation, for the device to expose a device interface
+    /// with the given GUID.
+    ///
+    /// Some WinUSB applications fail to find a device immediately after
+    /// installation, because Windows registers its device interface
+    /// asynchronously.  With this set, [`install`](DriverInstaller::install)
+    /// polls until the interface is registered, so the device can be opened
+    /// as soon as it returns.  If the interface doesn't appear within
+    /// `timeout`, [`Error::Timeout`](crate::Error::Timeout) is returned.
+    ///
+    /// The GUID must match the one the INF file registers - see
+    /// [`PrepareDriverOptions::device_guid`].
     ///
     /// # Examples
     ///
     /// ```no_run
+    /// use std::time::Duration;
     /// use wdi_rs::DriverInstaller;
     ///
-    /// let device = DriverInstaller::for_device(0x1234, 0x5678)
+    /// let installer = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .ensure_interface_registered(
+    ///         "{6e45736a-2b1b-4078-b772-b3af2b6fde1c}".parse()?,
+    ///         Duration::from_secs(10),
+    ///     );
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn ensure_interface_registered(mut self, guid: DeviceInterfaceGuid, timeout: Duration) -> Self {
+        debug!("Setting interface registration check: {} within {:?}", guid, timeout);
+        self.interface_registration = Some((guid, timeout));
+        self
+    }
+    
+    /// Wait, before installing, for the device to be ready to accept a driver.
+    ///
+    /// Devices can enumerate shortly after being plugged in while still in a
+    /// transitional state, causing installation to fail with
+    /// [`Error::Busy`](crate::Error::Busy).  With this set, the device's status
+    /// is polled until it has either started, or is waiting for a driver to
+    /// be installed.  If it doesn't settle within `timeout`,
+    /// [`Error::Timeout`](crate::Error::Timeout) is returned.  If the device's
+    /// status can't be read at all, such as when it has no instance ID, a
+    /// warning is logged and installation goes ahead without waiting.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let installer = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .wait_until_ready(Duration::from_secs(5));
+    /// ```
+    pub fn wait_until_ready(mut self, timeout: Duration) -> Self {
+        debug!("Setting wait until ready timeout to: {:?}", timeout);
+        self.ready_timeout = Some(timeout);
+        self
+    }
+    
+    /// Set a function to choose between multiple devices matching a
+    /// [`DeviceSelector::VidPid`] selector.
+    ///
+    /// By default the first matching device is used.  With a chooser set, it
+    /// is called with a [`DeviceChoice`] for each matching device, carrying
+    /// the information needed to present them to the user without further
+    /// queries.  It returns the index of the chosen device, or `None` to
+    /// cancel, in which case [`Error::UserCancel`](crate::Error::UserCancel)
+    /// is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let installer = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_chooser(|choices| {
+    ///         for (i, choice) in choices.iter().enumerate() {
+    ///             println!("{}: {} at {}", i, choice.device,
+    ///                      choice.location.as_deref().unwrap_or("unknown location"));
+    ///         }
+    ///         // Prompt the user...
+    ///         Some(0)
+    ///     });
+    /// ```
+    pub fn with_chooser(mut self, chooser: impl Fn(&[DeviceChoice]) -> Option<usize> + Send + 'static) -> Self {
+        debug!("Setting device chooser");
+        self.chooser = Some(Box::new(chooser));
+        self
+    }
+    
+    /// Set a callback to be invoked with the installed device after a
+    /// successful installation.
+    ///
+    /// After installing, devices are briefly allowed to settle and then
+    /// re-enumerated, so the callback receives a fresh [`Device`] showing the
+    /// newly bound driver.  This suits applications which want to act on the
+    /// device immediately, for example to open it.  If the device can't be
+    /// found on re-enumeration, the callback receives the device as it was
+    /// before installation.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .on_installed(Box::new(|device| {
+    ///         println!("{} now using driver {:?}", device, device.driver);
+    ///     }))
     ///     .install()?;
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn on_installed(mut self, f: Box<dyn Fn(&Device) + Send>) -> Self {
+        debug!("Setting on installed callback");
+        self.on_installed = Some(f);
+        self
+    }
+    
+    /// Set a callback to be invoked as the installation moves through each
+    /// [`InstallPhase`].
     ///
-    /// println!("Installed driver for device: {}", device);
+    /// Installing a driver can take over a minute, so this allows
+    /// applications, such as GUIs, to show progress without parsing log
+    /// output.  The callback is invoked on the installing thread - for
+    /// [`start_install`](DriverInstaller::start_install) and
+    /// [`install_spawn`](DriverInstaller::install_spawn), the worker thread,
+    /// other than [`InstallPhase::Enumerating`], which is reported from the
+    /// calling thread, where the device is found.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::{DriverInstaller, InstallPhase};
+    ///
+    /// DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_progress(Box::new(|phase| {
+    ///         let percent = match phase {
+    ///             InstallPhase::Enumerating => 0,
+    ///             InstallPhase::DeviceFound => 10,
+    ///             InstallPhase::Preparing => 20,
+    ///             InstallPhase::Installing => 40,
+    ///             InstallPhase::Completed => 100,
+    ///         };
+    ///         println!("{:?}: {}%", phase, percent);
+    ///     }))
+    ///     .install()?;
     /// # Ok::<(), wdi_rs::Error>(())
     /// ```
-    pub fn install(self) -> Result<Device, WdiError> {
+    pub fn with_progress(mut self, cb: Box<dyn Fn(InstallPhase) + Send>) -> Self {
+        debug!("Setting progress callback");
+        self.progress = Some(cb);
+        self
+    }
+    
+    /// Verify the driver survives the device being unplugged and plugged
+    /// back in.
+    ///
+    /// After installation, `prompt` is invoked with the installed device, and
+    /// should ask the user to replug it.  The installer then waits up to
+    /// `timeout` for the device to disappear and reappear, and checks it is
+    /// still bound to the installed driver - catching cases where Windows
+    /// prefers another driver when the device next enumerates.
+    ///
+    /// The check is skipped for [`DriverType::User`] drivers, other than
+    /// waiting for the replug, as the driver they install isn't known.  For
+    /// [`start_install`](DriverInstaller::start_install) and
+    /// [`install_spawn`](DriverInstaller::install_spawn), `prompt` is invoked
+    /// on the worker thread.
+    ///
+    /// # Errors
+    ///
+    /// The installation then fails with:
+    /// * `Error::Timeout` if the device isn't replugged within `timeout`.
+    /// * `Error::ConflictingDriver` if the replugged device is bound to a
+    ///   different driver.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .verify_across_replug(Duration::from_secs(60), Box::new(|device| {
+    ///         println!("Please unplug {} and plug it back in", device);
+    ///     }))
+    ///     .install()?;
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn verify_across_replug(mut self, timeout: Duration, prompt: Box<dyn Fn(&Device) + Send>) -> Self {
+        debug!("Setting replug verification with timeout {:?}", timeout);
+        self.replug = Some((timeout, prompt));
+        self
+    }
+    
+    /// Set the UI language for any dialogs Windows shows during installation.
+    ///
+    /// The calling thread's UI language is set to `lang`, a locale name such
+    /// as `de-DE`, for the duration of the installation and restored
+    /// afterwards.  Note that this only affects dialogs shown by Windows -
+    /// libwdi's own strings are not localized.
+    ///
+    /// If the language is not recognised, installation fails with
+    /// [`Error::InvalidParam`](crate::Error::InvalidParam).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let installer = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_ui_language("de-DE");
+    /// ```
+    pub fn with_ui_language(mut self, lang: &str) -> Self {
+        debug!("Setting UI language to: {}", lang);
+        self.ui_language = Some(lang.to_string());
+        self
+    }
+    
+    /// Supply the WDF coinstaller DLL to install with the driver, for
+    /// example when the one embedded in libwdi is missing or unsuitable for
+    /// the target OS.
+    ///
+    /// `version` is the KMDF version the coinstaller provides, e.g. `(1, 11)`
+    /// for `WdfCoInstaller01011.dll`, and `data` is the DLL itself.  After the
+    /// driver is prepared, the DLL is written to the architecture
+    /// subdirectory of the staging directory, and the INF's coinstaller
+    /// references and `KmdfLibraryVersion` are updated to match.  The driver
+    /// is then re-prepared, so the catalog covers the new files.  An external
+    /// INF isn't modified, so must already reference the coinstaller.
+    ///
+    /// Only drivers using WDF, such as libusbK, have coinstallers -
+    /// installation fails with [`Error::InvalidParam`](crate::Error::InvalidParam)
+    /// if the INF doesn't reference the coinstaller.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::{DriverInstaller, DriverType};
+    ///
+    /// let coinstaller = std::fs::read("WdfCoInstaller01011.dll").unwrap();
+    ///
+    /// let installer = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_driver_type(DriverType::LibUsbK)
+    ///     .with_coinstaller((1, 11), &coinstaller);
+    /// ```
+    pub fn with_coinstaller(mut self, version: (u16, u16), data: &[u8]) -> Self {
+        debug!("Setting WDF coinstaller version {}.{} ({} bytes)", version.0, version.1, data.len());
+        self.coinstaller = Some((version, data.to_vec()));
+        self
+    }
+    
+    /// Set whether to replace an existing driver even if it conflicts with
+    /// the driver being installed.
+    ///
+    /// By default, installation fails with
+    /// [`Error::ConflictingDriver`](crate::Error::ConflictingDriver) if the
+    /// device has a driver which isn't a compatible replacement - see
+    /// [`DriverType::is_compatible_replacement_for`].  With this set, the
+    /// driver is installed anyway, replacing whatever driver is bound.  This
+    /// is intended for development devices, as the existing driver may be a
+    /// vendor driver the device needs.  A warning is logged when a driver is
+    /// forcibly replaced.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let installer = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .force_replace(true);
+    /// ```
+    pub fn force_replace(mut self, yes: bool) -> Self {
+        debug!("Setting force replace to: {}", yes);
+        self.force_replace = yes;
+        self
+    }
+    
+    /// Set whether the driver replaces the device's function driver, or is
+    /// added alongside it as an upper filter driver - see [`InstallMode`].
+    ///
+    /// With [`InstallMode::Filter`], the existing function driver is left
+    /// bound, so the check that it can be replaced is skipped and
+    /// [`force_replace`](DriverInstaller::force_replace) has no effect.  The
+    /// installation only fails with `Error::AlreadyInstalled` if the filter
+    /// is already installed.  libwdi only supports libusb0 as a filter
+    /// driver, so other driver types fail with `Error::NotSupported`.
+    ///
+    /// `Filter` sets [`InstallDriverOptions::install_filter_driver`], whatever
+    /// value was set with
+    /// [`with_install_options`](DriverInstaller::with_install_options).
+    ///
+    /// Defaults to [`InstallMode::ReplaceFunction`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::{DriverInstaller, DriverType, InstallMode};
+    ///
+    /// DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_driver_type(DriverType::LibUsb0)
+    ///     .install_mode(InstallMode::Filter)
+    ///     .install()?;
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn install_mode(mut self, mode: InstallMode) -> Self {
+        debug!("Setting install mode to: {:?}", mode);
+        self.install_mode = mode;
+        self
+    }
+    
+    /// Only install if Windows hasn't already found a working driver for the
+    /// device.
+    ///
+    /// After the device is selected, the installation proceeds only if it
+    /// has no driver, a generic driver which doesn't provide its function,
+    /// or a driver which has failed - see
+    /// [`Device::has_functional_driver`].  Otherwise it fails with
+    /// [`Error::Exists`](crate::Error::Exists), whichever driver is bound.
+    /// This differs from the default check, which only skips devices already
+    /// using the requested driver, returning
+    /// [`Error::AlreadyInstalled`](crate::Error::AlreadyInstalled).
+    ///
+    /// This suits cautious installers which shouldn't touch devices Windows
+    /// has already handled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::{DriverInstaller, Error};
+    ///
+    /// match DriverInstaller::for_device(0x1234, 0x5678).only_if_unknown().install() {
+    ///     Ok(device) => println!("Installed driver for {}", device),
+    ///     Err(Error::Exists) => println!("Device already has a working driver"),
+    ///     Err(e) => return Err(e),
+    /// }
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn only_if_unknown(mut self) -> Self {
+        debug!("Setting only install if driver unknown");
+        self.only_if_unknown = true;
+        self
+    }
+    
+    /// Back up the device's existing driver package to a directory before
+    /// replacing it, giving a manual rollback path.
+    ///
+    /// When the device has a driver, its package is located in the driver
+    /// store via its published INF, and copied to a subdirectory of `path`
+    /// named after the package's driver store directory, e.g.
+    /// `usbser.inf_amd64_0123456789abcdef`.  If the backup fails, the
+    /// installation is aborted before anything is replaced.  The backup's
+    /// location is returned in [`InstallOutcome::backup_path`].
+    ///
+    /// To restore the driver, install the backed up INF, for example with
+    /// [`with_inf_file`](DriverInstaller::with_inf_file).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let outcome = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .force_replace(true)
+    ///     .backup_existing_to("C:\\DriverBackups".into())
+    ///     .install_with_outcome()?;
+    ///
+    /// if let Some(backup) = &outcome.backup_path {
+    ///     println!("Previous driver backed up to {}", backup.display());
+    /// }
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn backup_existing_to(mut self, path: PathBuf) -> Self {
+        debug!("Setting driver backup directory to: {}", path.display());
+        self.backup_dir = Some(path);
+        self
+    }
+    
+    /// Set the prefix used to name the temporary directory the driver files
+    /// are staged in.
+    ///
+    /// By default the directory has a random name, so directories left
+    /// behind by different applications (for example after a crash) can't be
+    /// told apart.  With a prefix set, the directory is named like
+    /// `myapp-XXXXXX`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let installer = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_temp_prefix("myapp-");
+    /// ```
+    pub fn with_temp_prefix(mut self, prefix: &str) -> Self {
+        debug!("Setting temporary directory prefix to: {}", prefix);
+        self.temp_prefix = Some(prefix.to_string());
+        self
+    }
+    
+    /// Set the directory the temporary directory the driver files are staged
+    /// in is created in.
+    ///
+    /// By default the system temporary directory is used, which in locked
+    /// down environments may not be writable, or may be on a slow network
+    /// share.  The directory must already exist - it is checked to exist and
+    /// be writable before installation starts.
+    ///
+    /// # Errors
+    ///
+    /// The installation then fails with `Error::Access` if `dir` doesn't
+    /// exist or isn't writable.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let installer = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_temp_dir("C:\\ProgramData\\MyApp\\tmp".into());
+    /// ```
+    pub fn with_temp_dir(mut self, dir: PathBuf) -> Self {
+        debug!("Setting temporary directory location to: {}", dir.display());
+        self.temp_dir = Some(dir);
+        self
+    }
+    
+    /// Retry the installation if another driver installation is in progress.
+    ///
+    /// libwdi fails with
+    /// [`Error::PendingInstallation`](crate::Error::PendingInstallation) or
+    /// [`Error::Busy`](crate::Error::Busy) while another installation is
+    /// under way, for example one Windows started itself when the device was
+    /// plugged in.  With this set, preparing and installing the driver is
+    /// retried up to `attempts` more times on either error, sleeping `delay`
+    /// between tries.  Any other error is returned immediately.  The number
+    /// of retries made is reported in [`InstallOutcome::retries`].
+    ///
+    /// By default installation is not retried.  Any
+    /// [`with_progress`](DriverInstaller::with_progress) callback sees the
+    /// [`InstallPhase::Preparing`] phase again on each retry.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let outcome = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_retry(3, Duration::from_secs(10))
+    ///     .install_with_outcome()?;
+    ///
+    /// println!("Installed after {} retries", outcome.retries);
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn with_retry(mut self, attempts: u32, delay: Duration) -> Self {
+        debug!("Setting retry: attempts={}, delay={:?}", attempts, delay);
+        self.retry = Some((attempts, delay));
+        self
+    }
+    
+    /// Set whether [`install_composite`](DriverInstaller::install_composite)
+    /// continues with the remaining interfaces after one fails.
+    ///
+    /// Composite devices often have an interface which can't or shouldn't be
+    /// driven, for example because it is already bound to a different driver.
+    /// With this enabled, each interface's result is collected in the
+    /// [`CompositeInstallReport`] rather than aborting on the first failure.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let report = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .best_effort(true)
+    ///     .install_composite()?;
+    ///
+    /// for (device, e) in &report.failed {
+    ///     println!("Failed to install driver for {}: {}", device, e);
+    /// }
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn best_effort(mut self, best_effort: bool) -> Self {
+        debug!("Setting best effort to: {}", best_effort);
+        self.best_effort = best_effort;
+        self
+    }
+    
+    /// Perform the driver installation, returning details of the outcome.
+    ///
+    /// This is the same as [`install`](DriverInstaller::install), but returns
+    /// an [`InstallOutcome`] with information about the installation, such
+    /// as the driver that was replaced.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let outcome = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .install_with_outcome()?;
+    ///
+    /// if let Some(inf) = &outcome.previous_driver_inf {
+    ///     println!("Replaced driver {}", inf);
+    /// }
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn install_with_outcome(mut self) -> Result<InstallOutcome, WdiError> {
         info!("Starting driver installation");
         debug!("Configuration: selector={:?}, driver_type={:?}, inf_source={:?}", 
                self.device_selector, self.driver_type, self.inf_source);
         
+        warn_if_reboot_pending();
+        self.check_driver_supported()?;
+        self.check_temp_dir()?;
+        
+        let start = Instant::now();
+        self.report_enumerating();
         let device = self.find_device()?;
+        let enumeration_time = start.elapsed();
+        debug!("Found device in {:?}", enumeration_time);
+        self.report(InstallPhase::DeviceFound);
+        
         self.check_existing_driver(&device)?;
-        self.prepare_and_install(device)
+        let mut outcome = self.prepare_and_install(device)?;
+        outcome.enumeration_time = enumeration_time;
+        outcome.elapsed = start.elapsed();
+        Ok(outcome)
     }
     
-    /// Find the target device based on the selector.
-    fn find_device(&self) -> Result<Device, WdiError> {
-        debug!("Finding target device");
+    /// Perform the driver installation, keeping the driver files afterwards.
+    ///
+    /// Normally the driver files, including the INF generated by libwdi for
+    /// [`InfSource::Generated`], are staged in a temporary directory which is
+    /// deleted once installation completes.  This instead keeps the
+    /// directory, so the files can be archived or inspected, for example for
+    /// auditing.
+    ///
+    /// Returns the device installed and the path of the INF file installed.
+    /// The INF's parent directory contains the other driver files.
+    ///
+    /// **The caller is responsible for deleting the directory** when it is no
+    /// longer needed.  For [`InfSource::External`], the files are prepared
+    /// alongside the existing INF, which is never deleted.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let (device, inf_path) = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .install_keep_files()?;
+    ///
+    /// println!("Installed {} from {}", device, inf_path.display());
+    ///
+    /// // ...archive the files, then clean up the temporary directory
+    /// if let Some(dir) = inf_path.parent() {
+    ///     std::fs::remove_dir_all(dir).ok();
+    /// }
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn install_keep_files(mut self) -> Result<(Device, PathBuf), WdiError> {
+        self.keep_files = true;
+        let outcome = self.install_with_outcome()?;
+        let inf_path = outcome.kept_inf_path.ok_or(WdiError::Other)?;
+        Ok((outcome.device, inf_path))
+    }
+    
+    /// Perform the driver installation, and on success append an
+    /// [`InstallRecord`](crate::InstallRecord) to the JSON state file at
+    /// `path`.
+    ///
+    /// The file is created if needed, and holds a JSON array of records.
+    /// Updates are serialized across processes using a `.lock` file
+    /// alongside it, and written atomically, so the file is never left
+    /// partially written.  The lock file is left in place afterwards, as
+    /// removing it could let two writers lock different files; it is empty
+    /// and can be ignored.  Deployment agents can read it back with
+    /// [`read_install_records`](crate::read_install_records) on later runs to
+    /// skip installations already done.
+    ///
+    /// Requires the `serde` feature.
+    ///
+    /// # Errors
+    ///
+    /// As [`install`](DriverInstaller::install).  Returns
+    /// [`Error::Resource`](crate::Error::Resource) if the driver was installed
+    /// but the record could not be written.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::path::PathBuf;
+    /// use wdi_rs::{read_install_records, DriverInstaller};
+    ///
+    /// let state = PathBuf::from("C:\\ProgramData\\MyApp\\drivers.json");
+    /// let records = read_install_records(&state)?;
+    ///
+    /// if !records.iter().any(|r| r.vid == 0x1234 && r.pid == 0x5678) {
+    ///     DriverInstaller::for_device(0x1234, 0x5678)
+    ///         .install_recording_to(state)?;
+    /// }
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn install_recording_to(self, path: PathBuf) -> Result<Device, WdiError> {
+        let driver_type = self.effective_driver_type();
+        let outcome = self.install_with_outcome()?;
         
-        match &self.device_selector {
-            DeviceSelector::Specific(device) => {
-                debug!("Using pre-selected device: {}", device);
-                Ok(device.clone())
-            }
+        let record = crate::record::InstallRecord::new(&outcome, driver_type);
+        crate::record::append_install_record(&path, record)?;
+        
+        Ok(outcome.device)
+    }
+    
+    /// Perform the driver installation.
+    ///
+    /// This will:
+    /// 1. Find the target device (if not already specified)
+    /// 2. Check if a driver is already installed
+    /// 3. Prepare the driver files
+    /// 4. Install the driver
+    ///
+    /// Returns the [`Device`] for the device that was installed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The device cannot be found
+    /// - The device was disconnected before installation started
+    /// - The requested driver is already installed
+    ///   ([`Error::AlreadyInstalled`](crate::Error::AlreadyInstalled))
+    /// - The existing driver can't be replaced by the requested one
+    ///   ([`Error::ConflictingDriver`](crate::Error::ConflictingDriver), see
+    ///   [`DriverType::is_compatible_replacement_for`])
+    /// - A custom INF file has no models section for the host architecture
+    /// - Driver preparation fails
+    /// - Driver installation fails
+    /// - File I/O operations fail
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let device = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .install()?;
+    ///
+    /// println!("Installed driver for device: {}", device);
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn install(self) -> Result<Device, WdiError> {
+        self.install_with_outcome().map(|outcome| outcome.device)
+    }
+    
+    /// Start the driver installation, returning a handle to poll for the
+    /// result rather than blocking.
+    ///
+    /// This suits single-threaded event loops, such as a UI's, which can
+    /// call [`InstallHandle::poll`] on each tick.  The device is found on the
+    /// calling thread, so any [`with_chooser`](DriverInstaller::with_chooser)
+    /// callback runs there.  As the libwdi calls themselves block, the
+    /// remainder of the installation runs on an internal worker thread, and
+    /// the handle surfaces its result.  Any
+    /// [`on_installed`](DriverInstaller::on_installed) callback is invoked
+    /// from `poll`, on the polling thread.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::task::Poll;
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let mut handle = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .start_install();
+    ///
+    /// loop {
+    ///     match handle.poll() {
+    ///         Poll::Ready(result) => {
+    ///             println!("Installation finished: {:?}", result);
+    ///             break;
+    ///         }
+    ///         Poll::Pending => {
+    ///             // Do other work, e.g. redraw the UI
+    ///             std::thread::sleep(std::time::Duration::from_millis(50));
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn start_install(mut self) -> InstallHandle {
+        info!("Starting driver installation in the background");
+        
+        let on_installed = self.on_installed.take();
+        self.report_enumerating();
+        let device = match self.find_device() {
+            Ok(device) => device,
+            Err(e) => return InstallHandle { state: HandleState::Finished(Some(Err(e))), on_installed },
+        };
+        
+        let worker = self.into_worker(device);
+        let reenumerate_installed = on_installed.is_some();
+        
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let result = worker.install();
+            let installed = match &result {
+                Ok(device) if reenumerate_installed => {
+                    thread::sleep(INSTALL_SETTLE_TIME);
+                    reenumerate(device)
+                }
+                _ => None,
+            };
             
-            DeviceSelector::VidPid { vid, pid } => {
-                debug!("Enumerating USB devices");
-                let opts = CreateListOptions {
-                    list_all: true,
-                    list_hubs: false,
-                    trim_whitespaces: true,
-                };
-                
-                let devices = create_list(opts)?;
-                trace!("Found {} USB devices", devices.len());
-                
-                if devices.is_empty() {
-                    error!("No USB devices found on the system");
-                    return Err(WdiError::NotFound);
+            // The handle may have been dropped, in which case nobody wants the result
+            let _ = tx.send((result, installed));
+        });
+        
+        InstallHandle { state: HandleState::Running(rx), on_installed }
+    }
+    
+    /// Perform the driver installation on a dedicated thread, returning its
+    /// [`JoinHandle`].
+    ///
+    /// `install_driver` can block for up to the
+    /// [pending installation timeout](crate::InstallDriverOptions::pending_install_timeout), so calling
+    /// [`install`](DriverInstaller::install) directly freezes a
+    /// single-threaded UI.  Instead, the caller can check
+    /// [`JoinHandle::is_finished`] on each tick, then `join` the handle for
+    /// the result.
+    ///
+    /// [`DeviceSelector::First`] and [`DeviceSelector::All`] predicates, and
+    /// any [`with_chooser`](DriverInstaller::with_chooser) callback, are
+    /// called on the calling thread, as the device is found there before the
+    /// thread is spawned - enumeration is quick compared to installation.
+    /// The remainder of the installation runs on the spawned thread, so any
+    /// [`on_installed`](DriverInstaller::on_installed),
+    /// [`with_progress`](DriverInstaller::with_progress) (other than for
+    /// [`InstallPhase::Enumerating`]) or
+    /// [`verify_across_replug`](DriverInstaller::verify_across_replug)
+    /// callback is invoked there - use
+    /// [`start_install`](DriverInstaller::start_install) for an
+    /// `on_installed` callback on the calling thread.
+    ///
+    /// If the device can't be found, the returned thread finishes
+    /// immediately with the error.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let handle = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .install_spawn();
+    ///
+    /// while !handle.is_finished() {
+    ///     // Do other work, e.g. redraw the UI
+    ///     std::thread::sleep(std::time::Duration::from_millis(50));
+    /// }
+    ///
+    /// let device = handle.join().expect("installation thread panicked")?;
+    /// println!("Installed driver for device: {}", device);
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn install_spawn(self) -> JoinHandle<Result<Device, WdiError>> {
+        info!("Spawning driver installation thread");
+        
+        self.report_enumerating();
+        let device = match self.find_device() {
+            Ok(device) => device,
+            Err(e) => return thread::spawn(move || Err(e)),
+        };
+        
+        let worker = self.into_worker(device);
+        thread::spawn(move || worker.install())
+    }
+    
+    /// Prepare the installer to install for the already found `device` on a
+    /// worker thread.
+    fn into_worker(mut self, device: Device) -> DriverInstaller {
+        self.chooser = None;
+        self.device_selector = DeviceSelector::Specific(device);
+        self
+    }
+    
+    /// Perform the driver installation on every interface of a composite
+    /// device.
+    ///
+    /// The device is selected as for [`install`](DriverInstaller::install),
+    /// and the driver is then installed on each of its interfaces that libwdi
+    /// enumerates, in interface number order.  If the selected device is not
+    /// composite, only it is installed.
+    ///
+    /// Interfaces are grouped by their [container ID](Device::container_id),
+    /// so every function of the physical device is included, even if it
+    /// presents more than one VID and PID.  If the container ID isn't
+    /// available, interfaces with the same VID, PID and parent are used.
+    ///
+    /// By default the first failure aborts the installation and is returned.
+    /// Use [`best_effort`](DriverInstaller::best_effort) to continue with the
+    /// remaining interfaces instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device cannot be found, or, unless
+    /// `best_effort` is set, if any interface fails to install.
+    pub fn install_composite(mut self) -> Result<CompositeInstallReport, WdiError> {
+        info!("Starting composite driver installation");
+        debug!("Configuration: selector={:?}, driver_type={:?}, inf_source={:?}, best_effort={}", 
+               self.device_selector, self.driver_type, self.inf_source, self.best_effort);
+        
+        warn_if_reboot_pending();
+        self.check_driver_supported()?;
+        self.check_temp_dir()?;
+        
+        let device = self.find_device()?;
+        let interfaces = composite_interfaces(&device)?;
+        info!("Installing driver on {} interface(s)", interfaces.len());
+        
+        let mut report = CompositeInstallReport::default();
+        for interface in interfaces {
+            let result = self.check_existing_driver(&interface)
+                .and_then(|_| self.prepare_and_install(interface.clone()));
+            
+            match result {
+                Ok(outcome) => report.installed.push(outcome.device),
+                Err(e) if self.best_effort => {
+                    warn!("Failed to install driver for interface {} - continuing: {}", interface.mi, e);
+                    report.failed.push((interface, e));
                 }
-                
-                let matching: Vec<_> = devices.iter()
-                    .filter(|d| d.vid == *vid && d.pid == *pid)
-                    .collect();
-                
-                if matching.is_empty() {
-                    error!("No USB devices found with VID:PID {:04x}:{:04x}", vid, pid);
-                    return Err(WdiError::NotFound);
+                Err(e) => {
+                    error!("Failed to install driver for interface {} - aborting: {}", interface.mi, e);
+                    return Err(e);
                 }
-                
+            }
+        }
+        
+        Ok(report)
+    }
+    
+    /// Install the driver on every device matching a
+    /// [`DeviceSelector::All`] selector.
+    ///
+    /// Devices are enumerated once, and the driver is then prepared and
+    /// installed for each matching device in turn.  A failure for one device
+    /// doesn't stop the others being installed - each device is returned
+    /// with its own result.  For other selectors, the single selected device
+    /// is installed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if enumeration fails or no devices match.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::{DriverInstaller, DeviceSelector};
+    ///
+    /// let results = DriverInstaller::new(
+    ///     DeviceSelector::All(Box::new(|dev| dev.vid == 0x1234 && dev.pid == 0x5678))
+    /// ).install_all()?;
+    ///
+    /// for (device, result) in results {
+    ///     match result {
+    ///         Ok(()) => println!("Installed driver for {}", device),
+    ///         Err(e) => println!("Failed to install driver for {}: {}", device, e),
+    ///     }
+    /// }
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn install_all(mut self) -> Result<DeviceResults, WdiError> {
+        info!("Starting driver installation on all matching devices");
+        debug!("Configuration: selector={:?}, driver_type={:?}, inf_source={:?}", 
+               self.device_selector, self.driver_type, self.inf_source);
+        
+        warn_if_reboot_pending();
+        self.check_driver_supported()?;
+        self.check_temp_dir()?;
+        
+        let devices = match &self.device_selector {
+            DeviceSelector::All(predicate) => self.find_all_matching(|d| predicate(d))?,
+            _ => vec![self.find_device()?],
+        };
+        info!("Installing driver on {} device(s)", devices.len());
+        
+        Ok(install_each(devices, |device| {
+            self.check_existing_driver(device)
+                .and_then(|_| self.prepare_and_install(device.clone()))
+                .map(|_| ())
+        }))
+    }
+    
+    /// Validate the driver package, without installing anything.
+    ///
+    /// This resolves the device, INF and options, stages the package and
+    /// prepares the driver exactly as [`install`](DriverInstaller::install)
+    /// would, but in a temporary directory, then verifies the signature of
+    /// the resulting catalog.  Nothing is added to the driver store or certificate stores.
+    /// The returned [`PackageValidation`] reports each check, and is intended
+    /// for CI pipelines checking whether a package will install cleanly.
+    ///
+    /// An external INF is copied to the temporary directory before
+    /// preparation, so its own directory isn't modified.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device cannot be found, the builder's settings
+    /// are inconsistent with the INF, or the package can't be staged.
+    /// Failures of the checks themselves, including preparing the driver,
+    /// are reported in the [`PackageValidation`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let validation = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_inf_file("driver/acme.inf".into())
+    ///     .validate_package()?;
+    ///
+    /// if !validation.is_valid() {
+    ///     println!("Package will not install cleanly: {:?}", validation);
+    /// }
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn validate_package(mut self) -> Result<PackageValidation, WdiError> {
+        info!("Validating driver package");
+        
+        self.check_temp_dir()?;
+        let device = self.find_device()?;
+        let package = self.resolve_package(&device)?;
+        let staged = self.stage_package(&package, true)?;
+        
+        let prepare_error = self.prepare_package(&device, &package, &staged.driver_path, &staged.inf_path).err();
+        if let Some(e) = &prepare_error {
+            warn!("Driver preparation failed during validation: {}", e);
+        }
+        
+        // Check the INF as prepared, which for generated INFs only exists now
+        let inf_data = fs::read(&staged.inf_path).ok();
+        let inf_syntax_valid = inf_data.as_deref().is_some_and(check_inf_syntax)
+            && !matches!(prepare_error, Some(WdiError::InfSyntax));
+        let architecture_compatible = inf_data.as_deref()
+            .is_some_and(|data| check_inf_architecture(data).is_ok());
+        
+        let catalog = fs::read_dir(&staged.driver_path).ok()
+            .and_then(|entries| entries.filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .find(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("cat"))));
+        let signature = match &catalog {
+            Some(catalog) => verify_signature(catalog),
+            None => SignatureStatus::NotChecked,
+        };
+        
+        let validation = PackageValidation {
+            device,
+            inf_syntax_valid,
+            architecture_compatible,
+            prepare_error,
+            catalog_name: catalog.and_then(|path| path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())),
+            signature,
+        };
+        info!("Package validation complete: valid={}", validation.is_valid());
+        Ok(validation)
+    }
+    
+    /// Check whether the selected device already has the driver installed,
+    /// without installing anything.
+    ///
+    /// This finds the device using the selector, exactly as
+    /// [`install`](DriverInstaller::install) would, and reports whether it is
+    /// already bound to the driver type being installed - WinUSB, unless
+    /// another type has been set.  This allows, for example, a GUI to disable
+    /// its "Install" button.  Always `false` for [`DriverType::User`], as the
+    /// driver can't be identified.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device cannot be found.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let installer = DriverInstaller::for_device(0x1234, 0x5678);
+    /// if installer.is_installed()? {
+    ///     println!("WinUSB is already installed");
+    /// } else {
+    ///     installer.install()?;
+    /// }
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn is_installed(&self) -> Result<bool, WdiError> {
+        let device = self.find_device()?;
+        let installed = self.has_driver_installed(&device);
+        debug!("Driver installed for {}: {}", device, installed);
+        Ok(installed)
+    }
+    
+    /// Check the INF is well-formed enough to install, without installing
+    /// anything.
+    ///
+    /// An embedded or external INF is checked to be valid UTF-16LE (with a
+    /// byte order mark) or UTF-8/ANSI text, with a `[Version]` section
+    /// containing a valid `Signature`.  This catches malformed INFs before
+    /// installation, where they otherwise fail deep inside libwdi with an
+    /// unexplained `Error::InfSyntax`.  The same checks are made by
+    /// [`install`](DriverInstaller::install) before preparing the driver.
+    ///
+    /// Generated INFs always pass, and an [`InfSource::Directory`] isn't
+    /// checked until installation selects an INF from it.  For a full check
+    /// of the prepared package, use
+    /// [`validate_package`](DriverInstaller::validate_package).
+    ///
+    /// # Errors
+    ///
+    /// * Returns `Error::InfSyntax` if the INF is malformed, logging why.
+    /// * Returns `Error::Resource` if an external INF file can't be read.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let installer = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_inf_data(include_bytes!("..\\inf\\sample.inf"), "sample.inf");
+    /// installer.validate_inf()?;
+    /// installer.install()?;
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn validate_inf(&self) -> Result<(), WdiError> {
+        match &self.inf_source {
+            InfSource::Embedded { data, filename } => check_inf_data(data, filename),
+            InfSource::External { path } => {
+                let data = fs::read(path)
+                    .map_err(|e| {
+                        error!("Failed to read external INF file {}: {}", path.display(), e);
+                        WdiError::Resource
+                    })?;
+                check_inf_data(&data, &path.display().to_string())
+            }
+            InfSource::Generated => Ok(()),
+            InfSource::Directory { path, .. } => {
+                debug!("INF in {} is selected at installation - not validating", path.display());
+                Ok(())
+            }
+        }
+    }
+    
+    /// Preview the installation, without installing anything.
+    ///
+    /// This resolves the target device, the INF to be used and the final
+    /// prepare and install options, exactly as
+    /// [`install`](DriverInstaller::install) would, but doesn't prepare or
+    /// install the driver or otherwise modify the system.  The returned
+    /// [`InstallPreview`] implements `Display`, giving a readable report
+    /// suitable for a "review before install" confirmation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device cannot be found, the builder's settings
+    /// are inconsistent with the INF, or an external INF file cannot be read.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::DriverInstaller;
+    ///
+    /// let preview = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_vendor_name("ACME Corp")
+    ///     .preview()?;
+    ///
+    /// println!("{}", preview);
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn preview(mut self) -> Result<InstallPreview, WdiError> {
+        info!("Previewing driver installation");
+        
+        let device = self.find_device()?;
+        let package = self.resolve_package(&device)?;
+        
+        let (inf_contents, driver_path) = match &package.inf {
+            PackageInf::Embedded { data, .. } => (Some(data.clone()), None),
+            PackageInf::External(path) => {
+                let data = fs::read(path)
+                    .map_err(|e| {
+                        error!("Failed to read external INF file {}: {}", path.display(), e);
+                        WdiError::Resource
+                    })?;
+                (Some(data), path.parent().map(PathBuf::from))
+            }
+            // libwdi only generates the INF when the driver is prepared
+            PackageInf::Generated => (None, None),
+        };
+        
+        Ok(InstallPreview {
+            device,
+            inf_contents,
+            prepare_opts: package.prepare_opts,
+            install_opts: self.options.install_opts,
+            driver_path,
+            inf_name: package.inf.name(),
+        })
+    }
+    
+    /// Find the target device based on the selector.
+    fn find_device(&self) -> Result<Device, WdiError> {
+        debug!("Finding target device");
+        
+        let device = match &self.device_selector {
+            DeviceSelector::Specific(device) => {
+                debug!("Using pre-selected device: {}", device);
+                return Ok(device.clone());
+            }
+            
+            DeviceSelector::VidPid { vid, pid } => {
+                let matching = self.find_all_matching(|d| d.vid == *vid && d.pid == *pid)?;
                 if matching.len() > 1 {
                     warn!("Multiple USB devices found with VID:PID {:04x}:{:04x}", vid, pid);
-                    info!("Using first device found");
+                    match &self.chooser {
+                        Some(chooser) => choose_device(chooser, matching)?,
+                        None => {
+                            info!("Using first device found");
+                            matching[0].clone()
+                        }
+                    }
+                } else {
+                    matching[0].clone()
                 }
-                
-                let device = matching[0].clone();
-                info!("Found target device: {}", device);
-                Ok(device)
             }
             
-            DeviceSelector::First(predicate) => {
-                debug!("Enumerating USB devices with predicate filter");
-                let opts = CreateListOptions {
-                    list_all: true,
-                    list_hubs: false,
-                    trim_whitespaces: true,
-                };
-                
-                let devices = create_list(opts)?;
+            DeviceSelector::First(predicate) | DeviceSelector::All(predicate) => {
+                self.find_matching(|d| predicate(d))?
+            }
+            
+            DeviceSelector::AnyVidPid(pairs) => {
+                self.find_matching(|d| pairs.contains(&(d.vid, d.pid)))?
+            }
+            
+            DeviceSelector::Index(index) => {
+                debug!("Enumerating USB devices to select index {}", index);
+                let devices = create_list(CreateListOptions::all())?;
                 trace!("Found {} USB devices", devices.len());
                 
-                if devices.is_empty() {
-                    error!("No USB devices found on the system");
-                    return Err(WdiError::NotFound);
-                }
-                
-                let device = devices.iter()
-                    .find(|d| predicate(d))
+                devices.get(*index)
                     .ok_or_else(|| {
-                        error!("No device matched the predicate");
+                        error!("No device at index {} ({} devices found)", index, devices.len());
                         WdiError::NotFound
                     })?
-                    .clone();
-                
-                info!("Found target device: {}", device);
-                Ok(device)
             }
+            
+            DeviceSelector::Serial { vid, pid, serial } => {
+                self.find_matching(|d| d.vid == *vid && d.pid == *pid && serial_matches(d, serial))?
+            }
+            
+            DeviceSelector::FriendlyName(name) => {
+                let wanted = name.to_lowercase();
+                self.find_matching(|d| {
+                    d.friendly_name().or_else(|| d.desc.clone())
+                        .is_some_and(|shown| shown.to_lowercase().contains(&wanted))
+                })?
+            }
+            
+            DeviceSelector::VidPidMi { vid, pid, mi } => {
+                self.find_matching(|d| d.vid == *vid && d.pid == *pid && d.is_composite && d.mi == *mi)?
+            }
+        };
+        
+        info!("Found target device: {}", device);
+        Ok(device)
+    }
+    
+    /// Enumerate the connected devices and return the first matching
+    /// `predicate`.
+    fn find_matching(&self, predicate: impl Fn(&Device) -> bool) -> Result<Device, WdiError> {
+        self.find_all_matching(predicate)?
+            .into_iter()
+            .next()
+            .ok_or(WdiError::NotFound)
+    }
+    
+    /// Enumerate the connected devices and return all those matching
+    /// `predicate`, in enumeration order.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotFound` if no device matches.
+    fn find_all_matching(&self, predicate: impl Fn(&Device) -> bool) -> Result<Vec<Device>, WdiError> {
+        debug!("Enumerating USB devices to find {:?}", self.device_selector);
+        let devices = create_list(CreateListOptions::all())?;
+        trace!("Found {} USB devices", devices.len());
+        
+        let matching: Vec<_> = devices.iter().filter(|d| predicate(d)).collect();
+        if matching.is_empty() {
+            error!("No USB device found matching {:?}", self.device_selector);
+            return Err(WdiError::NotFound);
+        }
+        Ok(matching)
+    }
+    
+    /// Check this build of libwdi supports the driver type being installed,
+    /// so that an unsupported type fails before anything is done.
+    fn check_driver_supported(&self) -> Result<(), WdiError> {
+        let driver_type = self.effective_driver_type();
+        if self.install_mode == InstallMode::Filter && driver_type != DriverType::LibUsb0 {
+            error!("Driver type {:?} can't be installed as a filter driver - only LibUsb0 can", driver_type);
+            return Err(WdiError::NotSupported);
+        }
+        if is_driver_supported(driver_type) {
+            Ok(())
+        } else {
+            error!("Driver type {:?} is not supported by this build of libwdi", driver_type);
+            Err(WdiError::NotSupported)
+        }
+    }
+    
+    /// The driver type that will be installed.
+    fn effective_driver_type(&self) -> DriverType {
+        self.driver_type.unwrap_or(self.options.prepare_opts.driver_type)
+    }
+    
+    /// Whether the device already has the driver type being installed.
+    fn has_driver_installed(&self, device: &Device) -> bool {
+        self.effective_driver_type().kind() == Some(device.driver_kind())
+    }
+    
+    /// Check whether the device's existing driver, if any, may be replaced
+    /// by the driver type being installed - see
+    /// [`DriverType::is_compatible_replacement_for`].
+    fn check_existing_driver(&self, device: &Device) -> Result<(), WdiError> {
+        self.check_existing_driver_with(device, Device::has_functional_driver)
+    }
+    
+    /// As [`check_existing_driver`](Self::check_existing_driver), using
+    /// `has_functional_driver` in place of querying Windows for the device's
+    /// status.
+    fn check_existing_driver_with(
+        &self,
+        device: &Device,
+        has_functional_driver: impl Fn(&Device) -> bool,
+    ) -> Result<(), WdiError> {
+        debug!("Checking existing driver for device: {}", device);
+        
+        let driver_type = self.effective_driver_type();
+        let current = device.driver_kind();
+        
+        if self.only_if_unknown && has_functional_driver(device) {
+            info!("Device already has working driver {:?} - not installing", current);
+            return Err(WdiError::Exists);
+        }
+        
+        if self.install_mode == InstallMode::Filter {
+            if device.installed_driver_types().contains(&driver_type) {
+                info!("Device already has {:?} installed - nothing to do", driver_type);
+                return Err(WdiError::AlreadyInstalled);
+            }
+            info!("Adding {:?} filter alongside existing driver {:?}", driver_type, current);
+            return Ok(());
+        }
+        
+        if current == DriverKind::None {
+            debug!("Device has no driver installed - proceeding");
+            return Ok(());
+        }
+        
+        if self.has_driver_installed(device) {
+            info!("Device already has {:?} driver installed - nothing to do", driver_type);
+            return Err(WdiError::AlreadyInstalled);
+        }
+        
+        if !driver_type.is_compatible_replacement_for(&current) && self.force_replace {
+            warn!("Forcing replacement of existing driver {:?} with {:?}", current, driver_type);
+            return Ok(());
+        }
+        
+        if !driver_type.is_compatible_replacement_for(&current) {
+            error!("Device has driver {:?} which can't be replaced by {:?}", current, driver_type);
+            error!("Cannot replace existing driver - manual uninstall required");
+            return Err(WdiError::ConflictingDriver {
+                current: device.driver.clone().unwrap_or_default(),
+            });
+        }
+        
+        info!("Replacing existing driver {:?} with {:?}", current, driver_type);
+        Ok(())
+    }
+    
+    /// Check the device is still connected, by re-enumerating and matching on
+    /// its device instance ID (or VID, PID and interface if it has none).
+    fn check_device_present(&self, device: &Device) -> Result<(), WdiError> {
+        debug!("Verifying device is still present: {}", device);
+        
+        let present = match create_list(CreateListOptions::all()) {
+            Ok(devices) => devices.iter().any(|d| same_device(device, &d)),
+            Err(WdiError::NoDevice) => false,
+            Err(e) => return Err(e),
+        };
+        
+        if !present {
+            error!("Device was disconnected before the driver could be installed: {}", device);
+            return Err(WdiError::NoDevice);
+        }
+        
+        Ok(())
+    }
+    
+    /// Apply explicit builder settings over the prepare options.
+    ///
+    /// See [`with_prepare_options`](DriverInstaller::with_prepare_options) for
+    /// the precedence order.
+    fn apply_builder_overrides(&mut self) {
+        if let Some(driver_type) = self.driver_type {
+            self.options.prepare_opts.driver_type = driver_type;
+        }
+        if let Some(vendor_name) = &self.vendor_name {
+            self.options.prepare_opts.vendor_name = Some(vendor_name.clone());
+        }
+        if let Some(guid) = self.device_guid {
+            self.options.prepare_opts.device_guid = Some(guid.to_string());
+        }
+        if let Some(wcid) = self.wcid {
+            self.options.prepare_opts.use_wcid_driver = wcid;
+        }
+        if self.install_mode == InstallMode::Filter {
+            self.options.install_opts.install_filter_driver = true;
+        }
+    }
+    
+    /// Whether this is an unsigned development install, with both catalog
+    /// creation and signing disabled in the prepare options.  Missing catalog
+    /// and signature errors from preparing the driver are then expected, and
+    /// don't fail the installation.
+    fn is_unsigned_install(&self) -> bool {
+        let opts = &self.options.prepare_opts;
+        opts.disable_cat && opts.disable_signing
+    }
+    
+    /// If no vendor name has been set, look it up from the device's VID.
+    fn apply_vendor_lookup(&self, device: &Device, opts: &mut PrepareDriverOptions) {
+        if !self.vendor_lookup || opts.vendor_name.is_some() {
+            return;
+        }
+        
+        match get_vendor_name(device.vid) {
+            Some(vendor_name) => {
+                debug!("Using vendor name from VID {:04x}: {}", device.vid, vendor_name);
+                opts.vendor_name = Some(vendor_name);
+            }
+            None => debug!("No vendor name known for VID {:04x}", device.vid),
+        }
+    }
+    
+    /// Rewrite the manufacturer and device description strings in a
+    /// libwdi-generated INF, then re-prepare it as an external INF so the
+    /// catalog matches the modified file.
+    ///
+    /// Without a custom description, the device's own description is
+    /// rewritten if it needs escaping, as libwdi substitutes it unescaped.
+    fn customize_generated_inf(
+        &self,
+        device: &Device,
+        driver_path: &str,
+        inf_path: &str,
+        opts: &PrepareDriverOptions,
+    ) -> Result<(), WdiError> {
+        debug!("Customizing strings in generated INF: {}", inf_path);
+        
+        let data = fs::read(inf_path)
+            .map_err(|e| {
+                error!("Failed to read generated INF file: {}", e);
+                WdiError::Resource
+            })?;
+        
+        let description = self.device_description.as_deref()
+            .or_else(|| device.desc.as_deref().filter(|desc| needs_inf_escaping(desc)));
+        let (text, replaced) = rewrite_inf_strings(
+            &inf_text(&data),
+            self.vendor_name.as_deref(),
+            description,
+        );
+        
+        if replaced == 0 {
+            warn!("No manufacturer or device description strings found in generated INF - leaving unchanged");
+            return Ok(());
+        }
+        
+        let data = encode_inf_like(&data, text);
+        
+        fs::write(inf_path, data)
+            .map_err(|e| {
+                error!("Failed to write generated INF file: {}", e);
+                WdiError::Resource
+            })?;
+        
+        debug!("Replaced {} string(s) in generated INF, re-preparing driver", replaced);
+        prepare_driver(
+            device,
+            driver_path,
+            inf_path,
+            &PrepareDriverOptions { external_inf: true, ..opts.clone() },
+        ).map_err(|e| {
+            error!("Failed to prepare customized driver: {}", e);
+            e
+        })
+    }
+    
+    /// Rewrite the prepared INF's `Class` and `ClassGuid` to the class set
+    /// with [`with_device_class`](DriverInstaller::with_device_class), then
+    /// re-prepare the driver as an external INF so the catalog covers it.
+    fn apply_device_class(
+        &self,
+        device: &Device,
+        driver_path: &str,
+        inf_path: &str,
+        opts: &PrepareDriverOptions,
+    ) -> Result<(), WdiError> {
+        let Some((name, guid)) = &self.device_class else {
+            return Ok(());
+        };
+        debug!("Setting device class in INF to: {} {}", name, guid);
+        
+        let data = fs::read(inf_path)
+            .map_err(|e| {
+                error!("Failed to read INF file: {}", e);
+                WdiError::Resource
+            })?;
+        let Some(text) = rewrite_inf_class(&inf_text(&data), name, *guid) else {
+            error!("INF has no [Version] section to set the device class in");
+            return Err(WdiError::InvalidParam);
+        };
+        
+        fs::write(inf_path, encode_inf_like(&data, text))
+            .map_err(|e| {
+                error!("Failed to write INF file: {}", e);
+                WdiError::Resource
+            })?;
+        
+        prepare_driver(
+            device,
+            driver_path,
+            inf_path,
+            &PrepareDriverOptions { external_inf: true, ..opts.clone() },
+        ).map_err(|e| {
+            error!("Failed to prepare driver with device class: {}", e);
+            e
+        })
+    }
+    
+    /// Write the supplied WDF coinstaller to the driver directory and, if
+    /// `patch_inf` is set, update the INF to reference it.  The driver is then
+    /// re-prepared as an external INF so the catalog covers the new files.
+    fn install_coinstaller(
+        &self,
+        device: &Device,
+        driver_path: &str,
+        inf_path: &str,
+        patch_inf: bool,
+        opts: &PrepareDriverOptions,
+    ) -> Result<(), WdiError> {
+        let Some((version, dll)) = &self.coinstaller else {
+            return Ok(());
+        };
+        let filename = coinstaller_filename(*version);
+        debug!("Installing WDF coinstaller: {}", filename);
+        
+        let data = fs::read(inf_path)
+            .map_err(|e| {
+                error!("Failed to read INF file: {}", e);
+                WdiError::Resource
+            })?;
+        let mut text = inf_text(&data);
+        
+        if patch_inf {
+            let (patched, replaced) = patch_coinstaller(&text, *version);
+            debug!("Replaced {} coinstaller reference(s) in INF", replaced);
+            text = patched;
+        }
+        
+        if !text.to_ascii_lowercase().contains(&filename.to_ascii_lowercase()) {
+            error!("INF does not reference the WDF coinstaller {}", filename);
+            return Err(WdiError::InvalidParam);
+        }
+        
+        if patch_inf {
+            fs::write(inf_path, encode_inf_like(&data, text))
+                .map_err(|e| {
+                    error!("Failed to write INF file: {}", e);
+                    WdiError::Resource
+                })?;
+        }
+        
+        // libwdi stages coinstallers in a subdirectory per architecture
+        let arch = host_inf_arch().trim_start_matches("nt");
+        let arch_dir = Path::new(driver_path).join(arch);
+        fs::create_dir_all(&arch_dir)
+            .and_then(|_| fs::write(arch_dir.join(&filename), dll))
+            .map_err(|e| {
+                error!("Failed to write WDF coinstaller: {}", e);
+                WdiError::Resource
+            })?;
+        
+        debug!("Re-preparing driver with WDF coinstaller");
+        prepare_driver(
+            device,
+            driver_path,
+            inf_path,
+            &PrepareDriverOptions { external_inf: true, ..opts.clone() },
+        ).map_err(|e| {
+            error!("Failed to prepare driver with WDF coinstaller: {}", e);
+            e
+        })
+    }
+    
+    /// Resolve the INF and prepare options for installing on `device`, and
+    /// check the builder's settings are consistent with them.
+    ///
+    /// This is shared by installation, [`validate_package`] and [`preview`],
+    /// so they agree on what would be installed.
+    ///
+    /// [`validate_package`]: DriverInstaller::validate_package
+    /// [`preview`]: DriverInstaller::preview
+    fn resolve_package(&mut self, device: &Device) -> Result<ResolvedPackage, WdiError> {
+        let inf = match &self.inf_source {
+            InfSource::Embedded { data, filename } => PackageInf::Embedded {
+                data: data.clone(),
+                filename: filename.clone(),
+            },
+            InfSource::External { path } => PackageInf::External(path.clone()),
+            InfSource::Generated => PackageInf::Generated,
+            InfSource::Directory { path, selection } => {
+                PackageInf::External(select_inf(path, device, *selection)?)
+            }
+        };
+        
+        // Catch packaging mistakes before anything is staged
+        if let Some((name, _)) = &self.device_class {
+            if !is_valid_class_name(name) {
+                error!("Invalid device class name: {:?}", name);
+                return Err(WdiError::InvalidParam);
+            }
+            if matches!(inf, PackageInf::External(_)) {
+                error!("Device class can only be overridden with an embedded or generated INF");
+                return Err(WdiError::InvalidParam);
+            }
+        }
+        
+        if !self.additional_files.is_empty() {
+            if matches!(inf, PackageInf::External(_)) {
+                error!("Additional files can only be staged with an embedded or generated INF");
+                return Err(WdiError::InvalidParam);
+            }
+            check_staged_paths(&inf.name(), &self.additional_files)?;
+        }
+        
+        // Set external_inf based on INF source, warning if user tried to set
+        // it.  This is per installation, so applied to a copy of the options.
+        let should_use_external_inf = !matches!(inf, PackageInf::Generated);
+        
+        if self.options.prepare_opts.external_inf != should_use_external_inf {
+            warn!("Overriding prepare_opts.external_inf (was {}, setting to {}) based on InF source",
+                  self.options.prepare_opts.external_inf, should_use_external_inf);
+        }
+        
+        self.apply_builder_overrides();
+        let mut prepare_opts = PrepareDriverOptions {
+            external_inf: should_use_external_inf,
+            ..self.options.prepare_opts.clone()
+        };
+        self.apply_vendor_lookup(device, &mut prepare_opts);
+        
+        match (prepare_opts.use_wcid_driver, device.supports_wcid()) {
+            (true, Some(false)) => warn!("WCID driver requested, but device doesn't advertise a Microsoft OS descriptor - the driver may not bind"),
+            (false, Some(true)) => debug!("Device advertises a Microsoft OS descriptor - consider with_wcid"),
+            _ => {}
+        }
+        if prepare_opts.use_wcid_driver && should_use_external_inf {
+            warn!("WCID driver requested with a supplied INF - the INF's own device matching may conflict");
+        }
+        
+        Ok(ResolvedPackage { inf, prepare_opts })
+    }
+    
+    /// Stage the package's INF, and any additional files, for libwdi to
+    /// prepare.
+    ///
+    /// An external INF is prepared in place, in its own directory, unless
+    /// `copy_external` is set, when it is first copied to a temporary
+    /// directory so its directory isn't modified.
+    fn stage_package(&self, package: &ResolvedPackage, copy_external: bool) -> Result<StagedPackage, WdiError> {
+        let (dir, temp_dir) = match &package.inf {
+            PackageInf::External(path) if !copy_external => {
+                debug!("Using external INF file: {}", path.display());
+                
+                if !path.exists() {
+                    error!("External INF file does not exist: {}", path.display());
+                    return Err(WdiError::NotFound);
+                }
+                
+                let dir = path.parent()
+                    .ok_or_else(|| {
+                        error!("Invalid external INF path - no parent directory");
+                        WdiError::InvalidParam
+                    })?
+                    .to_path_buf();
+                (dir, None)
+            }
+            _ => {
+                let temp_dir = self.create_temp_dir()?;
+                (temp_dir.path().to_path_buf(), Some(temp_dir))
+            }
+        };
+        
+        let inf_file_path = dir.join(package.inf.name());
+        match &package.inf {
+            PackageInf::Embedded { data, .. } => {
+                debug!("Writing INF file to: {}", inf_file_path.display());
+                fs::write(&inf_file_path, data)
+                    .map_err(|e| {
+                        error!("Failed to write INF file: {}", e);
+                        WdiError::Resource
+                    })?;
+                info!("INF file written successfully");
+            }
+            PackageInf::External(path) if copy_external => {
+                if path.file_name().is_none() {
+                    error!("Invalid external INF path - no filename");
+                    return Err(WdiError::InvalidParam);
+                }
+                fs::copy(path, &inf_file_path)
+                    .map_err(|e| {
+                        error!("Failed to copy external INF file {}: {}", path.display(), e);
+                        WdiError::Resource
+                    })?;
+            }
+            // Prepared in place, or generated by libwdi
+            PackageInf::External(_) | PackageInf::Generated => {}
+        }
+        
+        for (path, data) in &self.additional_files {
+            let target = dir.join(path);
+            debug!("Staging additional file: {}", target.display());
+            target.parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(&target, data))
+                .map_err(|e| {
+                    error!("Failed to stage {}: {}", target.display(), e);
+                    WdiError::Resource
+                })?;
+        }
+        
+        let (Some(driver_path), Some(inf_path)) = (dir.to_str(), inf_file_path.to_str()) else {
+            error!("Failed to convert driver path to string");
+            return Err(WdiError::InvalidParam);
+        };
+        
+        Ok(StagedPackage {
+            driver_path: driver_path.to_string(),
+            inf_path: inf_path.to_string(),
+            temp_dir,
+        })
+    }
+    
+    /// Prepare a staged package with libwdi, then apply any INF
+    /// customizations, device class and coinstaller.
+    fn prepare_package(
+        &self,
+        device: &Device,
+        package: &ResolvedPackage,
+        driver_path: &str,
+        inf_path: &str,
+    ) -> Result<(), WdiError> {
+        debug!("Preparing driver in: {}", driver_path);
+        debug!("INF path: {}", inf_path);
+        
+        let prepare_opts = &package.prepare_opts;
+        match prepare_driver(device, driver_path, inf_path, prepare_opts) {
+            Ok(()) => info!("Driver prepared successfully"),
+            Err(e @ (WdiError::CatMissing | WdiError::Unsigned)) if self.is_unsigned_install() => {
+                warn!("Ignoring {} preparing unsigned driver, as catalog and signing are disabled", e);
+            }
+            Err(e) => {
+                error!("Failed to prepare driver: {}", e);
+                return Err(e);
+            }
+        }
+        
+        // libwdi substitutes the device's description into the generated INF
+        // as is, so it must be rewritten if it contains special characters
+        if matches!(package.inf, PackageInf::Generated)
+            && (self.vendor_name.is_some()
+                || self.device_description.is_some()
+                || device.desc.as_deref().is_some_and(needs_inf_escaping))
+        {
+            self.customize_generated_inf(device, driver_path, inf_path, prepare_opts)?;
+        }
+        
+        if self.device_class.is_some() {
+            self.apply_device_class(device, driver_path, inf_path, prepare_opts)?;
+        }
+        
+        if self.coinstaller.is_some() {
+            let patch_inf = !matches!(package.inf, PackageInf::External(_));
+            self.install_coinstaller(device, driver_path, inf_path, patch_inf, prepare_opts)?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Create the temporary directory driver files are staged in.
+    fn create_temp_dir(&self) -> Result<TempDir, WdiError> {
+        let mut builder = tempfile::Builder::new();
+        if let Some(prefix) = &self.temp_prefix {
+            builder.prefix(prefix);
+        }
+        
+        match &self.temp_dir {
+            Some(dir) => builder.tempdir_in(dir)
+                .map_err(|e| {
+                    error!("Failed to create temporary directory in {}: {}", dir.display(), e);
+                    WdiError::Access
+                }),
+            None => builder.tempdir()
+                .map_err(|e| {
+                    error!("Failed to create temporary directory: {}", e);
+                    WdiError::Resource
+                }),
+        }
+    }
+    
+    /// Check the directory set with
+    /// [`with_temp_dir`](DriverInstaller::with_temp_dir), if any, exists and
+    /// is writable.
+    fn check_temp_dir(&self) -> Result<(), WdiError> {
+        let Some(dir) = &self.temp_dir else {
+            return Ok(());
+        };
+        
+        if !dir.is_dir() {
+            error!("Temporary directory location {} doesn't exist or isn't a directory", dir.display());
+            return Err(WdiError::Access);
+        }
+        tempfile::tempfile_in(dir)
+            .map(drop)
+            .map_err(|e| {
+                error!("Temporary directory location {} isn't writable: {}", dir.display(), e);
+                WdiError::Access
+            })
+    }
+    
+    /// Prepare and install the driver, retrying if configured with
+    /// [`with_retry`](DriverInstaller::with_retry).
+    fn prepare_and_install(&mut self, device: Device) -> Result<InstallOutcome, WdiError> {
+        let start = Instant::now();
+        let (attempts, delay) = self.retry.unwrap_or((0, Duration::ZERO));
+        
+        let mut retries = 0;
+        let mut outcome = loop {
+            match self.prepare_and_install_once(device.clone()) {
+                Err(e @ (WdiError::PendingInstallation | WdiError::Busy)) if retries < attempts => {
+                    retries += 1;
+                    warn!("Installation failed: {} - retrying in {:?} ({}/{})", e, delay, retries, attempts);
+                    thread::sleep(delay);
+                }
+                result => break result?,
+            }
+        };
+        
+        outcome.retries = retries;
+        outcome.elapsed = start.elapsed();
+        Ok(outcome)
+    }
+    
+    /// Prepare and install the driver, once.
+    fn prepare_and_install_once(&mut self, device: Device) -> Result<InstallOutcome, WdiError> {
+        info!("Preparing and installing driver for device: {}", device);
+        let start = Instant::now();
+        self.report(InstallPhase::Preparing);
+        
+        if self.verify_present {
+            self.check_device_present(&device)?;
+        }
+        
+        if let Some(timeout) = self.ready_timeout {
+            wait_for_ready(&device, timeout)?;
+        }
+        
+        // Restored when dropped at the end of the installation
+        let _ui_language = self.ui_language.as_deref()
+            .map(ThreadUiLanguage::set)
+            .transpose()?;
+        
+        let package = self.resolve_package(&device)?;
+        
+        // Catch malformed INFs, and INFs packaged for a different
+        // architecture, before libwdi does
+        match &package.inf {
+            PackageInf::Embedded { data, filename } => {
+                check_inf_data(data, filename)?;
+                check_inf_architecture(data)?;
+            }
+            PackageInf::External(path) => {
+                let data = fs::read(path)
+                    .map_err(|e| {
+                        error!("Failed to read external INF file: {}", e);
+                        WdiError::Resource
+                    })?;
+                check_inf_data(&data, &path.display().to_string())?;
+                check_inf_architecture(&data)?;
+            }
+            PackageInf::Generated => {}
+        }
+        
+        let StagedPackage { driver_path, inf_path, temp_dir: _temp_dir } = self.stage_package(&package, false)?;
+        self.prepare_package(&device, &package, &driver_path, &inf_path)?;
+        let unsigned = self.is_unsigned_install();
+        
+        // Record the driver being replaced, if any, so it can be rolled back to
+        let (previous_driver_inf, previous_driver_version) = if device.driver.is_some() {
+            let inf = device.driver_inf_name();
+            let version = device.driver_version_string();
+            info!("Replacing existing driver: inf={:?}, version={:?}", inf, version);
+            (inf, version)
+        } else {
+            (None, None)
+        };
+        
+        let backup_path = match (&self.backup_dir, device.driver.is_some()) {
+            (Some(dir), true) => {
+                let Some(inf) = &previous_driver_inf else {
+                    error!("Unable to find the existing driver's INF to back it up - aborting");
+                    return Err(WdiError::NotFound);
+                };
+                Some(backup_driver_package(inf, dir)?)
+            }
+            _ => None,
+        };
+        
+        // Install the driver
+        debug!("Installing driver");
+        self.report(InstallPhase::Installing);
+        
+        if unsigned {
+            warn!("Installing an unsigned driver without a catalog - this requires test signing to be enabled");
+        }
+        
+        install_driver(
+            &device,
+            &driver_path,
+            &inf_path,
+            &self.options.install_opts,
+        ).map_err(|e| {
+            error!("Failed to install driver: {}", e);
+            if unsigned && matches!(e, WdiError::CatMissing | WdiError::Unsigned) {
+                error!("Windows rejected the unsigned driver - check test signing is enabled (bcdedit /set testsigning on)");
+            }
+            e
+        })?;
+        
+        info!("Driver installed successfully");
+        
+        if let Some((guid, timeout)) = self.interface_registration {
+            wait_for_interface(&device, guid, timeout)?;
+        }
+        
+        if let Some((timeout, prompt)) = &self.replug {
+            thread::sleep(INSTALL_SETTLE_TIME);
+            prompt(&device);
+            let replugged = wait_for_replug(&device, *timeout)?;
+            check_driver_kind(&replugged, self.options.prepare_opts.driver_type)?;
+        }
+        
+        // Keep temp_dir alive until here so it doesn't get cleaned up prematurely
+        let kept_inf_path = if self.keep_files {
+            if let Some(temp_dir) = _temp_dir {
+                let dir = temp_dir.keep();
+                info!("Keeping driver files in: {}", dir.display());
+            }
+            Some(PathBuf::from(&inf_path))
+        } else {
+            drop(_temp_dir);
+            None
+        };
+        
+        let winusb_ready = matches!(self.options.prepare_opts.driver_type, DriverType::WinUsb)
+            && check_winusb_ready(&device);
+        
+        // Windows may have ranked a different INF above the one staged
+        let selected_inf = device.driver_inf_name();
+        debug!("Device bound to INF: {:?}", selected_inf);
+        
+        if let Some(on_installed) = &self.on_installed {
+            thread::sleep(INSTALL_SETTLE_TIME);
+            let installed = reenumerate(&device).unwrap_or_else(|| {
+                warn!("Installed device not found on re-enumeration - using pre-install details");
+                device.clone()
+            });
+            on_installed(&installed);
+        }
+        
+        let elapsed = start.elapsed();
+        info!("Installation took {:?}", elapsed);
+        self.report(InstallPhase::Completed);
+        
+        Ok(InstallOutcome {
+            device,
+            previous_driver_inf,
+            previous_driver_version,
+            winusb_ready,
+            selected_inf,
+            kept_inf_path,
+            elapsed,
+            retries: 0,
+            enumeration_time: Duration::ZERO,
+            backup_path,
+        })
+    }
+    
+    /// Invoke the progress callback, if set, with the phase just entered.
+    fn report(&self, phase: InstallPhase) {
+        trace!("Installation phase: {:?}", phase);
+        if let Some(progress) = &self.progress {
+            progress(phase);
+        }
+    }
+    
+    /// Report [`InstallPhase::Enumerating`], unless the device has already
+    /// been selected, in which case no enumeration takes place.  A worker
+    /// thread's installer always has a specific device, so the phase is only
+    /// reported once, from the thread which enumerates.
+    fn report_enumerating(&self) {
+        if !matches!(self.device_selector, DeviceSelector::Specific(_)) {
+            self.report(InstallPhase::Enumerating);
+        }
+    }
+}
+
+/// A handle to a driver installation running in the background, as
+/// returned by [`DriverInstaller::start_install`]
+pub struct InstallHandle {
+    state: HandleState,
+    on_installed: Option<InstalledCallback>,
+}
+
+enum HandleState {
+    Running(Receiver<(Result<Device, WdiError>, Option<Device>)>),
+    Finished(Option<Result<Device, WdiError>>),
+}
+
+impl InstallHandle {
+    /// Check whether the installation has finished, without blocking.
+    ///
+    /// Returns `Poll::Pending` while the installation is in progress, then
+    /// `Poll::Ready` with the installed device or the error, once.  Polling
+    /// again after that returns
+    /// [`Error::InvalidParam`](crate::Error::InvalidParam).
+    pub fn poll(&mut self) -> Poll<Result<Device, WdiError>> {
+        match &mut self.state {
+            HandleState::Running(rx) => match rx.try_recv() {
+                Ok((result, installed)) => {
+                    self.state = HandleState::Finished(None);
+                    if let (Ok(device), Some(on_installed)) = (&result, &self.on_installed) {
+                        let installed = installed.unwrap_or_else(|| {
+                            warn!("Installed device not found on re-enumeration - using pre-install details");
+                            device.clone()
+                        });
+                        on_installed(&installed);
+                    }
+                    Poll::Ready(result)
+                }
+                Err(TryRecvError::Empty) => Poll::Pending,
+                Err(TryRecvError::Disconnected) => {
+                    error!("Installation worker thread exited without a result");
+                    self.state = HandleState::Finished(None);
+                    Poll::Ready(Err(WdiError::Other))
+                }
+            },
+            HandleState::Finished(result) => match result.take() {
+                Some(result) => Poll::Ready(result),
+                None => {
+                    error!("Installation result already returned");
+                    Poll::Ready(Err(WdiError::InvalidParam))
+                }
+            },
+        }
+    }
+}
+
+impl fmt::Debug for InstallHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match &self.state {
+            HandleState::Running(_) => "Running",
+            HandleState::Finished(_) => "Finished",
+        };
+        f.debug_struct("InstallHandle")
+            .field("state", &state)
+            .field("on_installed", &self.on_installed.as_ref().map(|_| "<callback>"))
+            .finish()
+    }
+}
+
+/// Whether an installation replaces the device's function driver, or adds a
+/// filter driver alongside it - see [`DriverInstaller::install_mode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InstallMode {
+    /// Replace the device's function driver - the default
+    #[default]
+    ReplaceFunction,
+    /// Install as an upper filter driver, leaving the existing function
+    /// driver bound.  Only supported for libusb0.
+    Filter,
+}
+
+/// A phase of a driver installation, as reported to the callback set with
+/// [`DriverInstaller::with_progress`].
+///
+/// Phases are reported in order as each is entered.  If the installation
+/// fails, no further phases are reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallPhase {
+    /// Enumerating USB devices to find the one to install for.  Not reported
+    /// when the installer was given a [`DeviceSelector::Specific`] device, as
+    /// no enumeration takes place.
+    Enumerating,
+    /// The device has been found, and any existing driver is being checked
+    DeviceFound,
+    /// The driver files are being prepared
+    Preparing,
+    /// The driver is being installed - typically the longest phase
+    Installing,
+    /// The driver has been installed
+    Completed,
+}
+
+/// Details of a successful installation, as returned by
+/// [`DriverInstaller::install_with_outcome`]
+#[derive(Debug, Clone)]
+pub struct InstallOutcome {
+    /// The device the driver was installed for
+    pub device: Device,
+    /// The published INF name (e.g. `oem12.inf`) of the driver that was
+    /// replaced, if the device had one.  Together with
+    /// `previous_driver_version`, this allows rolling back to the exact prior
+    /// driver.
+    pub previous_driver_inf: Option<String>,
+    /// The version of the driver that was replaced, if the device had one
+    pub previous_driver_version: Option<String>,
+    /// Whether the device was verified as usable via WinUSB after
+    /// installation - bound to the WinUSB service, started without a problem,
+    /// and with a device interface GUID registered.  Always `false` for
+    /// other driver types.
+    ///
+    /// If installation reported success but this is `false`, the reason is
+    /// logged.
+    pub winusb_ready: bool,
+    /// The published INF name (e.g. `oem13.inf`) Windows actually bound the
+    /// device to after installation, if it could be read.
+    ///
+    /// Windows ranks all matching INFs, so a system INF may be chosen over
+    /// the one installed.  If so, this identifies the driver the device ended
+    /// up on.
+    pub selected_inf: Option<String>,
+    /// The path of the INF file installed, if the driver files were kept -
+    /// see [`DriverInstaller::install_keep_files`]
+    pub kept_inf_path: Option<PathBuf>,
+    /// How long the installation took in total, including
+    /// `enumeration_time`
+    pub elapsed: Duration,
+    /// How many times installation was retried after a transient failure -
+    /// always 0 unless retries were enabled with
+    /// [`DriverInstaller::with_retry`]
+    pub retries: u32,
+    /// How long was spent enumerating devices to find the target device -
+    /// negligible if the device was already known, as with
+    /// [`DriverInstaller::for_specific_device`]
+    pub enumeration_time: Duration,
+    /// Where the replaced driver's package was backed up to, if a backup was
+    /// requested - see [`DriverInstaller::backup_existing_to`]
+    pub backup_path: Option<PathBuf>,
+}
+
+/// Verify that a device is usable via WinUSB after installation, logging
+/// which expectation failed if not.
+fn check_winusb_ready(device: &Device) -> bool {
+    debug!("Verifying WinUSB is ready for device: {}", device);
+    
+    let Some(info) = device.device_id.as_deref().and_then(DeviceInfo::open) else {
+        warn!("WinUSB not ready: unable to query device {}", device);
+        return false;
+    };
+    
+    let service = info.property_string(&DEVPKEY_DEVICE_SERVICE);
+    if DriverKind::from_driver(service.as_deref()) != DriverKind::WinUsb {
+        warn!("WinUSB not ready: device is bound to service {:?}", service);
+        return false;
+    }
+    
+    let problem = info.property_u32(&DEVPKEY_DEVICE_PROBLEM_CODE).unwrap_or(0);
+    if problem != 0 {
+        warn!("WinUSB not ready: device has problem code {}", problem);
+        return false;
+    }
+    
+    let status = info.property_u32(&DEVPKEY_DEVICE_DEV_NODE_STATUS).unwrap_or(0);
+    if status & DN_STARTED == 0 {
+        warn!("WinUSB not ready: device has not started (status {:#010x})", status);
+        return false;
+    }
+    
+    if !info.registry_value_exists("DeviceInterfaceGUIDs")
+        && !info.registry_value_exists("DeviceInterfaceGUID")
+    {
+        warn!("WinUSB not ready: no device interface GUID registered");
+        return false;
+    }
+    
+    debug!("WinUSB is ready");
+    true
+}
+
+/// A candidate device passed to the chooser set with
+/// [`DriverInstaller::with_chooser`], with the details needed to present it
+/// to the user
+#[derive(Debug, Clone)]
+pub struct DeviceChoice {
+    /// The candidate device
+    pub device: Device,
+    /// The vendor name for the device's VID, from libwdi's database
+    pub vendor_name: Option<String>,
+    /// The device's location (e.g. `Port_#0002.Hub_#0001`), to distinguish
+    /// otherwise identical devices
+    pub location: Option<String>,
+    /// The device's current driver
+    pub driver_kind: DriverKind,
+    /// Whether the device already has a working function driver - see
+    /// [`Device::has_functional_driver`]
+    pub has_functional_driver: bool,
+}
+
+/// Present the matching devices to the chooser and return the chosen one.
+fn choose_device(
+    chooser: &dyn Fn(&[DeviceChoice]) -> Option<usize>,
+    devices: Vec<Device>,
+) -> Result<Device, WdiError> {
+    let mut choices: Vec<_> = devices.into_iter()
+        .map(|device| DeviceChoice {
+            vendor_name: get_vendor_name(device.vid),
+            location: device.location(),
+            driver_kind: device.driver_kind(),
+            has_functional_driver: device.has_functional_driver(),
+            device,
+        })
+        .collect();
+    
+    match chooser(&choices) {
+        Some(index) if index < choices.len() => {
+            info!("Chooser selected device {}", index);
+            Ok(choices.swap_remove(index).device)
+        }
+        Some(index) => {
+            error!("Chooser returned invalid index {} ({} choices)", index, choices.len());
+            Err(WdiError::InvalidParam)
+        }
+        None => {
+            info!("Chooser cancelled device selection");
+            Err(WdiError::UserCancel)
+        }
+    }
+}
+
+/// Install drivers for a batch of devices, each with its own driver type and
+/// options.
+///
+/// Devices are enumerated once, and each entry's target is resolved against
+/// that enumeration - so a heterogeneous set of devices (for example, some
+/// needing WinUSB and some CDC) can be installed without an enumeration pass
+/// per entry.  For the same reason, the presence check described in
+/// [`DriverInstaller::verify_present_before_install`] is skipped.
+///
+/// Entries are processed in order, and one failing does not prevent the
+/// others being attempted.  The driver type given in each entry takes
+/// precedence over the `driver_type` in its options.
+///
+/// Returns the result for each entry, in the same order, or an error if
+/// enumeration fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// use wdi_rs::{install_targets_typed, DeviceTarget, DriverType, InstallOptions};
+///
+/// let results = install_targets_typed(vec![
+///     (DeviceTarget::VidPid { vid: 0x1234, pid: 0x5678 }, DriverType::WinUsb, InstallOptions::default()),
+///     (DeviceTarget::VidPid { vid: 0x1234, pid: 0x9abc }, DriverType::Cdc, InstallOptions::default()),
+/// ])?;
+///
+/// for result in results {
+///     match result {
+///         Ok(device) => println!("Installed driver for {}", device),
+///         Err(e) => println!("Failed: {}", e),
+///     }
+/// }
+/// # Ok::<(), wdi_rs::Error>(())
+/// ```
+pub fn install_targets_typed(
+    entries: Vec<(DeviceTarget, DriverType, InstallOptions)>,
+) -> Result<Vec<Result<Device, WdiError>>, WdiError> {
+    let entries = entries.into_iter()
+        .map(|(target, driver_type, options)| (target, (driver_type, options)))
+        .collect();
+    let results = install_targets_with(entries, |installer, (driver_type, options)| {
+        let mut installer = installer.with_driver_type(driver_type);
+        installer.options = options;
+        installer
+    })?;
+    
+    Ok(results.into_iter().map(|(_, result)| result).collect())
+}
+
+/// Install drivers for a batch of targets from a single enumeration, as
+/// [`install_targets_typed`] describes, with each matched device's installer
+/// set up by `configure` from its entry's settings.
+pub(crate) fn install_targets_with<T>(
+    entries: Vec<(DeviceTarget, T)>,
+    configure: impl Fn(DriverInstaller, T) -> DriverInstaller,
+) -> Result<TargetResults, WdiError> {
+    info!("Installing drivers for {} target(s)", entries.len());
+    
+    let devices: Vec<Device> = create_list(CreateListOptions::all())?.iter().collect();
+    trace!("Found {} USB devices", devices.len());
+    
+    let results = entries.into_iter()
+        .map(|(target, settings)| {
+            let result = match devices.iter().find(|d| target.matches(d)) {
+                Some(device) => {
+                    let installer = DriverInstaller::for_specific_device(device.clone())
+                        .verify_present_before_install(false);
+                    configure(installer, settings).install().inspect_err(|e| {
+                        error!("Failed to install driver for target {}: {}", target, e);
+                    })
+                }
+                None => {
+                    error!("No device found for target {}", target);
+                    Err(WdiError::NotFound)
+                }
+            };
+            (target, result)
+        })
+        .collect();
+    
+    Ok(results)
+}
+
+/// Resolve a batch of targets to the devices currently connected.
+///
+/// Devices are enumerated once, and each target is matched against that
+/// enumeration.  Nothing is installed - this is the read-only companion to
+/// [`install_targets_typed`], allowing an application to show which of its
+/// known devices are connected before installing.
+///
+/// Returns each target with the first device it matches, or `None` if no
+/// matching device is connected, in the same order as `targets`.  Returns an
+/// error if enumeration fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// use wdi_rs::{resolve_targets, DeviceTarget};
+///
+/// let targets = [
+///     DeviceTarget::Usb { vid: 0x1234, pid: 0x5678, mi: None, serial: Some("ABC123".to_string()) },
+///     DeviceTarget::Usb { vid: 0x1234, pid: 0x9abc, mi: Some(1), serial: None },
+/// ];
+///
+/// for (target, device) in resolve_targets(&targets)? {
+///     match device {
+///         Some(device) => println!("{} is connected: {}", target, device),
+///         None => println!("{} is not connected", target),
+///     }
+/// }
+/// # Ok::<(), wdi_rs::Error>(())
+/// ```
+pub fn resolve_targets(targets: &[DeviceTarget]) -> Result<Vec<(DeviceTarget, Option<Device>)>, WdiError> {
+    debug!("Resolving {} target(s)", targets.len());
+    
+    let devices = match create_list(CreateListOptions::all()) {
+        Ok(devices) => devices.iter().collect(),
+        Err(WdiError::NoDevice) => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    trace!("Found {} USB devices", devices.len());
+    
+    Ok(targets.iter()
+        .map(|target| {
+            let device = devices.iter().find(|d| target.matches(d)).cloned();
+            if device.is_none() {
+                debug!("No device found for target {}", target);
+            }
+            (target.clone(), device)
+        })
+        .collect())
+}
+
+/// Result of [`DriverInstaller::install_composite`]
+#[derive(Debug, Default)]
+pub struct CompositeInstallReport {
+    /// Interfaces the driver was installed on
+    pub installed: Vec<Device>,
+    /// Interfaces the driver failed to install on, and why.  Only populated
+    /// when [`DriverInstaller::best_effort`] is set.
+    pub failed: Vec<(Device, WdiError)>,
+}
+
+/// The results of validating a driver package, as returned by
+/// [`DriverInstaller::validate_package`]
+#[derive(Debug)]
+pub struct PackageValidation {
+    /// The device the package was validated for
+    pub device: Device,
+    /// Whether the INF has a valid `[Version]` section, and libwdi didn't
+    /// report a syntax error
+    pub inf_syntax_valid: bool,
+    /// Whether the INF has a models section for this host's architecture
+    pub architecture_compatible: bool,
+    /// The error preparing the driver, if preparation failed
+    pub prepare_error: Option<WdiError>,
+    /// The filename of the catalog produced, if any
+    pub catalog_name: Option<String>,
+    /// The result of verifying the catalog's signature
+    pub signature: SignatureStatus,
+}
+
+impl PackageValidation {
+    /// Whether every check passed, so the package should install cleanly.
+    ///
+    /// Catalogs self-signed by libwdi are reported as
+    /// [`SignatureStatus::Untrusted`] until installed, so are accepted here.
+    /// Unsigned catalogs are rejected, as Windows won't install them.
+    pub fn is_valid(&self) -> bool {
+        self.inf_syntax_valid
+            && self.architecture_compatible
+            && self.prepare_error.is_none()
+            && self.signature != SignatureStatus::Unsigned
+    }
+}
+
+/// What an installation would do, as returned by
+/// [`DriverInstaller::preview`]
+#[derive(Debug, Clone)]
+pub struct InstallPreview {
+    /// The device the driver would be installed for
+    pub device: Device,
+    /// The contents of the INF file to be installed.  `None` for
+    /// [`InfSource::Generated`], as libwdi only generates the INF when the
+    /// driver is prepared.
+    pub inf_contents: Option<Vec<u8>>,
+    /// The fully resolved options the driver would be prepared with
+    pub prepare_opts: PrepareDriverOptions,
+    /// The fully resolved options the driver would be installed with
+    pub install_opts: InstallDriverOptions,
+    /// The directory the driver would be prepared in, or `None` if a
+    /// temporary directory would be created at install time
+    pub driver_path: Option<PathBuf>,
+    /// The filename of the INF within the driver directory
+    pub inf_name: String,
+}
+
+impl fmt::Display for InstallPreview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prepare = &self.prepare_opts;
+        
+        writeln!(f, "Device:            {}", self.device)?;
+        writeln!(f, "Current driver:    {}", self.device.driver.as_deref().unwrap_or("(none)"))?;
+        writeln!(f, "Driver type:       {:?}", prepare.driver_type)?;
+        writeln!(f, "Vendor name:       {}", prepare.vendor_name.as_deref().unwrap_or("(default)"))?;
+        writeln!(f, "Device GUID:       {}", prepare.device_guid.as_deref().unwrap_or("(default)"))?;
+        writeln!(f, "External INF:      {}", prepare.external_inf)?;
+        writeln!(f, "Catalog disabled:  {}", prepare.disable_cat)?;
+        writeln!(f, "Signing disabled:  {}", prepare.disable_signing)?;
+        writeln!(f, "Cert subject:      {}", prepare.cert_subject.as_deref().unwrap_or("(default)"))?;
+        writeln!(f, "WCID driver:       {}", prepare.use_wcid_driver)?;
+        writeln!(f, "Filter driver:     {}", self.install_opts.install_filter_driver)?;
+        writeln!(f, "Pending timeout:   {}ms", self.install_opts.pending_install_timeout)?;
+        match &self.driver_path {
+            Some(path) => writeln!(f, "Driver path:       {}", path.display())?,
+            None => writeln!(f, "Driver path:       (temporary directory)")?,
+        }
+        writeln!(f, "INF name:          {}", self.inf_name)?;
+        match &self.inf_contents {
+            Some(data) => write!(f, "INF contents:\n{}", inf_text(data)),
+            None => write!(f, "INF contents:      (generated by libwdi during installation)"),
+        }
+    }
+}
+
+fn warn_if_reboot_pending() {
+    if is_reboot_pending() {
+        warn!("A reboot is pending from a previous operation - installation may fail or be incomplete");
+    }
+}
+
+/// Find all enumerated interfaces of the composite device the given device is
+/// part of, sorted by interface number.
+///
+/// Interfaces are grouped by container ID where Windows provides a specific
+/// one, falling back to matching VID, PID and parent.
+fn composite_interfaces(device: &Device) -> Result<Vec<Device>, WdiError> {
+    if !device.is_composite {
+        debug!("Device is not composite: {}", device);
+        return Ok(vec![device.clone()]);
+    }
+    
+    let devices = create_list(CreateListOptions::all())?;
+    let container_id = device.container_id()
+        .filter(|id| !id.eq_ignore_ascii_case(Device::NULL_CONTAINER_ID));
+    let mut interfaces: Vec<_> = match &container_id {
+        Some(container_id) => devices.iter()
+            .filter(|d| d.is_composite)
+            .filter(|d| d.container_id().is_some_and(|id| id.eq_ignore_ascii_case(container_id)))
+            .collect(),
+        None => {
+            let parent_id = device.parent_id();
+            devices.iter()
+                .filter(|d| d.is_composite && d.vid == device.vid && d.pid == device.pid)
+                .filter(|d| parent_id.is_none() || d.parent_id() == parent_id)
+                .collect()
+        }
+    };
+    interfaces.sort_by_key(|d| (d.mi, d.vid, d.pid));
+    
+    trace!("Found {} interfaces of composite device {} (container {:?})", interfaces.len(), device, container_id);
+    Ok(interfaces)
+}
+
+/// Whether two devices are the same physical device (or interface), by
+/// device instance ID, or VID, PID and interface if either has no ID.
+fn same_device(a: &Device, b: &Device) -> bool {
+    match (&a.device_id, &b.device_id) {
+        (Some(id), Some(other)) => id.eq_ignore_ascii_case(other),
+        _ => a.vid == b.vid && a.pid == b.pid && a.mi == b.mi,
+    }
+}
+
+/// How long to let a device settle after installation before
+/// re-enumerating it.
+const INSTALL_SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Re-enumerate devices and return the fresh details of the given device.
+fn reenumerate(device: &Device) -> Option<Device> {
+    create_list(CreateListOptions::all()).ok()?
+        .iter()
+        .find(|d| same_device(device, d))
+}
+
+/// Install on each device in turn with `install`, carrying on past any
+/// failures, and return each device with its result.
+fn install_each(
+    devices: Vec<Device>,
+    mut install: impl FnMut(&Device) -> Result<(), WdiError>,
+) -> DeviceResults {
+    let mut results = Vec::with_capacity(devices.len());
+    for device in devices {
+        let result = install(&device);
+        
+        if let Err(e) = &result {
+            warn!("Failed to install driver for {} - continuing: {}", device, e);
+        }
+        results.push((device, result));
+    }
+    
+    results
+}
+
+/// How often to check device status while waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Check whether a device has settled - either started, or waiting for a
+/// driver to be installed.
+fn device_ready(device_id: &str) -> bool {
+    // The device may briefly disappear while it settles
+    let Some(info) = DeviceInfo::open(device_id) else {
+        return false;
+    };
+    
+    let status = info.property_u32(&DEVPKEY_DEVICE_DEV_NODE_STATUS).unwrap_or(0);
+    if status & DN_STARTED != 0 {
+        return true;
+    }
+    
+    if status & DN_HAS_PROBLEM != 0 {
+        let problem = info.property_u32(&DEVPKEY_DEVICE_PROBLEM_CODE).unwrap_or(0);
+        trace!("Device has problem code {}", problem);
+        return matches!(problem, CM_PROB_NOT_CONFIGURED | CM_PROB_REINSTALL | CM_PROB_FAILED_INSTALL);
+    }
+    
+    false
+}
+
+/// Poll until the device is ready to accept a driver.
+fn wait_for_ready(device: &Device, timeout: Duration) -> Result<(), WdiError> {
+    debug!("Waiting up to {:?} for device to be ready: {}", timeout, device);
+    
+    // Without the device's status there's nothing to wait for, so carry on
+    // and let the installation report any problem
+    let Some(device_id) = device.device_id.as_deref().filter(|id| DeviceInfo::open(id).is_some()) else {
+        warn!("Can't read device status - not waiting for it to be ready: {}", device);
+        return Ok(());
+    };
+    
+    let start = Instant::now();
+    while !device_ready(device_id) {
+        if start.elapsed() >= timeout {
+            error!("Device did not become ready within {:?}: {}", timeout, device);
+            return Err(WdiError::Timeout);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    
+    debug!("Device ready after {:?}", start.elapsed());
+    Ok(())
+}
+
+/// Poll until the device disappears and then reappears, returning its fresh
+/// details.
+fn wait_for_replug(device: &Device, timeout: Duration) -> Result<Device, WdiError> {
+    info!("Waiting up to {:?} for device to be replugged: {}", timeout, device);
+    
+    let start = Instant::now();
+    while reenumerate(device).is_some() {
+        if start.elapsed() >= timeout {
+            error!("Device was not unplugged within {:?}: {}", timeout, device);
+            return Err(WdiError::Timeout);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    debug!("Device unplugged after {:?}", start.elapsed());
+    
+    loop {
+        if let Some(replugged) = reenumerate(device) {
+            info!("Device replugged after {:?}", start.elapsed());
+            return Ok(replugged);
+        }
+        if start.elapsed() >= timeout {
+            error!("Device was not plugged back in within {:?}: {}", timeout, device);
+            return Err(WdiError::Timeout);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Check the device is bound to the given driver type's driver.  Always
+/// succeeds for `DriverType::User`, whose driver isn't known.
+fn check_driver_kind(device: &Device, driver_type: DriverType) -> Result<(), WdiError> {
+    let Some(expected) = driver_type.kind() else {
+        debug!("Not checking driver for user driver type");
+        return Ok(());
+    };
+    
+    let current = device.driver_kind();
+    if current != expected {
+        error!("Device is bound to {:?} rather than {:?}: {}", current, expected, device);
+        return Err(WdiError::ConflictingDriver {
+            current: device.driver.clone().unwrap_or_default(),
+        });
+    }
+    
+    debug!("Device still bound to {:?}", expected);
+    Ok(())
+}
+
+/// Poll until the device exposes a device interface with the given GUID.
+fn wait_for_interface(device: &Device, guid: DeviceInterfaceGuid, timeout: Duration) -> Result<(), WdiError> {
+    debug!("Waiting up to {:?} for device interface {} to be registered", timeout, guid);
+    
+    if device.device_id.is_none() {
+        warn!("Device has no instance ID - waiting for interface {} on any device", guid);
+    }
+    
+    let start = Instant::now();
+    let guid_ffi = guid.to_ffi();
+    while !interface_registered(device.device_id.as_deref(), &guid_ffi) {
+        if start.elapsed() >= timeout {
+            error!("Device interface {} was not registered within {:?}", guid, timeout);
+            return Err(WdiError::Timeout);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    
+    info!("Device interface {} registered after {:?}", guid, start.elapsed());
+    Ok(())
+}
+
+/// Decode INF file contents, which may be UTF-16LE (with BOM) or UTF-8/ANSI.
+fn inf_text(data: &[u8]) -> String {
+    if let Some(body) = data.strip_prefix(&[0xFF, 0xFE]) {
+        let units: Vec<u16> = body.chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        let body = data.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(data);
+        String::from_utf8_lossy(body).into_owned()
+    }
+}
+
+/// The INF architecture decoration (e.g. `NTamd64`) for the host, lowercased.
+fn host_inf_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86" => "ntx86",
+        "aarch64" => "ntarm64",
+        _ => "ntamd64",
+    }
+}
+
+/// An INF file's `DriverVer`, as (version, (year, month, day)).
+type DriverVer = ([u16; 4], (u16, u16, u16));
+
+/// Choose the INF file in `dir` matching the device, per `selection`.
+fn select_inf(dir: &Path, device: &Device, selection: InfSelection) -> Result<PathBuf, WdiError> {
+    debug!("Searching for INF files matching {} in {}", device, dir.display());
+    
+    let entries = fs::read_dir(dir)
+        .map_err(|e| {
+            error!("Failed to read INF directory {}: {}", dir.display(), e);
+            WdiError::NotFound
+        })?;
+    
+    let mut candidates: Vec<(PathBuf, Option<DriverVer>)> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("inf")))
+        .filter_map(|path| {
+            let text = inf_text(&fs::read(&path).ok()?);
+            inf_matches_device(&text, device).then(|| (path, parse_driver_ver(&text)))
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.0.cmp(&b.0));
+    
+    let names: Vec<_> = candidates.iter().map(|(path, _)| path.display().to_string()).collect();
+    debug!("Matching INF files: {:?}", names);
+    
+    let chosen = match (candidates.len(), selection) {
+        (0, _) => {
+            error!("No INF file in {} matches {}", dir.display(), device);
+            return Err(WdiError::NotFound);
+        }
+        (1, _) | (_, InfSelection::FirstMatch) => candidates.swap_remove(0),
+        (_, InfSelection::Error) => {
+            error!("Multiple INF files match {}: {:?}", device, names);
+            return Err(WdiError::InvalidParam);
+        }
+        (_, InfSelection::NewestVersion) => candidates.into_iter()
+            .max_by_key(|(_, ver)| ver.map(|(version, date)| (version, date)))
+            .expect("candidates is not empty"),
+        (_, InfSelection::Newest) => candidates.into_iter()
+            .max_by_key(|(_, ver)| ver.map(|(version, date)| (date, version)))
+            .expect("candidates is not empty"),
+    };
+    
+    info!("Using INF file: {}", chosen.0.display());
+    Ok(chosen.0)
+}
+
+/// Whether the INF text references the device's hardware ID - including the
+/// interface number for an interface of a composite device.
+fn inf_matches_device(text: &str, device: &Device) -> bool {
+    let text = text.to_ascii_uppercase();
+    let hardware_id = format!("USB\\VID_{:04X}&PID_{:04X}", device.vid, device.pid);
+    let interface = format!("&MI_{:02X}", device.mi);
+    
+    text.match_indices(&hardware_id).any(|(index, _)| {
+        let rest = &text[index + hardware_id.len()..];
+        if device.is_composite && rest.starts_with(&interface) {
+            return true;
+        }
+        !rest.starts_with('&')
+    })
+}
+
+/// Parse an INF's `DriverVer = mm/dd/yyyy[,w.x.y.z]` directive.  Returns
+/// `None` if missing or unparseable, such as libwdi's placeholders.
+fn parse_driver_ver(text: &str) -> Option<DriverVer> {
+    let value = text.lines()
+        .map(|line| line.split(';').next().unwrap_or("").trim())
+        .find_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            key.trim().eq_ignore_ascii_case("DriverVer").then_some(value)
+        })?;
+    
+    let (date, version) = value.split_once(',').unwrap_or((value, ""));
+    let mut date = date.trim().split('/').map(|part| part.parse::<u16>().ok());
+    let (month, day, year) = (date.next()??, date.next()??, date.next()??);
+    
+    let mut parts = [0u16; 4];
+    for (part, value) in parts.iter_mut().zip(version.trim().split('.')) {
+        *part = value.trim().parse().unwrap_or(0);
+    }
+    
+    Some((parts, (year, month, day)))
+}
+
+/// Check the files to be staged alongside the INF `inf_name` are at valid
+/// relative paths which don't collide, compared case-insensitively.
+fn check_staged_paths(inf_name: &str, files: &[(PathBuf, Vec<u8>)]) -> Result<(), WdiError> {
+    let mut staged = HashMap::new();
+    staged.insert(inf_name.to_lowercase(), Path::new(inf_name));
+    
+    for (path, _) in files {
+        let mut key = Vec::new();
+        for component in path.components() {
+            match component {
+                Component::Normal(part) => key.push(part.to_string_lossy().to_lowercase()),
+                Component::CurDir => {}
+                _ => {
+                    error!("Invalid path for staged file {} - must be relative, without ..", path.display());
+                    return Err(WdiError::InvalidParam);
+                }
+            }
+        }
+        if key.is_empty() {
+            error!("Invalid empty path for staged file");
+            return Err(WdiError::InvalidParam);
+        }
+        
+        if let Some(existing) = staged.insert(key.join("\\"), path) {
+            error!("Staged files {} and {} collide", existing.display(), path.display());
+            return Err(WdiError::InvalidParam);
+        }
+    }
+    
+    Ok(())
+}
+
+/// Copy a driver package, identified by its published INF name, from the
+/// driver store to a subdirectory of `dir`.  Returns the subdirectory.
+fn backup_driver_package(inf_name: &str, dir: &Path) -> Result<PathBuf, WdiError> {
+    debug!("Backing up driver package {} to {}", inf_name, dir.display());
+    
+    let package_dir = driver_store_inf(inf_name)
+        .as_deref()
+        .and_then(Path::parent)
+        .map(Path::to_path_buf)
+        .ok_or_else(|| {
+            error!("Driver package for {} not found in the driver store", inf_name);
+            WdiError::NotFound
+        })?;
+    let dest = dir.join(package_dir.file_name().unwrap_or(inf_name.as_ref()));
+    
+    copy_dir(&package_dir, &dest).map_err(|e| {
+        error!("Failed to back up driver package {} to {}: {}", package_dir.display(), dest.display(), e);
+        WdiError::Resource
+    })?;
+    
+    info!("Backed up driver package {} to {}", inf_name, dest.display());
+    Ok(dest)
+}
+
+/// Recursively copy the contents of `src` into `dest`, creating it.
+fn copy_dir(src: &Path, dest: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dest)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether a device's serial number matches, per [`DeviceSelector::Serial`].
+fn serial_matches(device: &Device, serial: &str) -> bool {
+    let matches = |s: &str| s.eq_ignore_ascii_case(serial);
+    
+    if device.device_id.as_deref().and_then(serial_from_instance_id).is_some_and(matches) {
+        return true;
+    }
+    device.is_composite && device.serial_number().is_some_and(|s| matches(&s))
+}
+
+/// Encode INF text in the same encoding as `original` - UTF-16LE if it has
+/// a byte order mark, otherwise UTF-8.
+fn encode_inf_like(original: &[u8], text: String) -> Vec<u8> {
+    if original.starts_with(&[0xFF, 0xFE]) {
+        let mut out = vec![0xFF, 0xFE];
+        out.extend(text.encode_utf16().flat_map(|c| c.to_le_bytes()));
+        out
+    } else {
+        text.into_bytes()
+    }
+}
+
+/// The filename of the WDF coinstaller for a KMDF version, e.g.
+/// `WdfCoInstaller01011.dll` for 1.11.
+fn coinstaller_filename((major, minor): (u16, u16)) -> String {
+    format!("WdfCoInstaller{:02}{:03}.dll", major, minor)
+}
+
+/// Replace references to any WDF coinstaller, and the `KmdfLibraryVersion`,
+/// with those for `version`.  Returns the new text and the number of
+/// replacements.
+fn patch_coinstaller(text: &str, version: (u16, u16)) -> (String, usize) {
+    const PREFIX: &str = "wdfcoinstaller";
+    let filename = coinstaller_filename(version);
+    let mut replaced = 0;
+    
+    // ASCII lowercasing preserves byte offsets, so indices into `lower` are
+    // valid in `text`, and fall on character boundaries around the ASCII name
+    let lower = text.to_ascii_lowercase();
+    let name_len = PREFIX.len() + 5 + ".dll".len();
+    let mut out = String::with_capacity(text.len());
+    let mut pos = 0;
+    while let Some(found) = lower[pos..].find(PREFIX) {
+        let index = pos + found;
+        let candidate = &text.as_bytes()[index..];
+        let is_name = candidate.len() >= name_len
+            && candidate[PREFIX.len()..PREFIX.len() + 5].iter().all(u8::is_ascii_digit)
+            && candidate[PREFIX.len() + 5..name_len].eq_ignore_ascii_case(b".dll");
+        
+        out.push_str(&text[pos..index]);
+        if is_name {
+            out.push_str(&filename);
+            pos = index + name_len;
+            replaced += 1;
+        } else {
+            out.push_str(&text[index..index + PREFIX.len()]);
+            pos = index + PREFIX.len();
+        }
+    }
+    out.push_str(&text[pos..]);
+    
+    let lines: Vec<String> = out.split('\n')
+        .map(|line| match line.split_once('=') {
+            Some((key, _)) if key.trim().eq_ignore_ascii_case("KmdfLibraryVersion") => {
+                replaced += 1;
+                format!("{}= {}.{}", key, version.0, version.1)
+            }
+            _ => line.to_string(),
+        })
+        .collect();
+    
+    (lines.join("\n"), replaced)
+}
+
+/// Whether a setup class name is non-empty and safe to write as an INF value.
+fn is_valid_class_name(name: &str) -> bool {
+    !name.trim().is_empty()
+        && !name.contains(|c: char| c.is_control() || matches!(c, ';' | '"' | '%' | '=' | '[' | ']'))
+}
+
+/// Replace the `Class` and `ClassGuid` entries in an INF's `[Version]`
+/// section, adding them if missing.  Returns `None` if there is no
+/// `[Version]` section.
+fn rewrite_inf_class(text: &str, name: &str, guid: DeviceInterfaceGuid) -> Option<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut in_version = false;
+    let mut found = false;
+    
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_version = trimmed.trim_start_matches('[').trim_end_matches(']').trim().eq_ignore_ascii_case("version");
+            out.push_str(line);
+            if in_version && !found {
+                found = true;
+                let eol = if line.ends_with("\r\n") { "\r\n" } else { "\n" };
+                out.push_str(&format!("Class = {}{}ClassGuid = {}{}", name, eol, guid, eol));
+            }
+            continue;
         }
-    }
-    
-    /// Check if the device already has a driver installed.
-    fn check_existing_driver(&self, device: &Device) -> Result<(), WdiError> {
-        debug!("Checking existing driver for device: {}", device);
         
-        if let Some(driver) = &device.driver {
-            if driver.starts_with("WinUSB") {
-                info!("Device already has WinUSB driver installed - nothing to do");
-                return Err(WdiError::Exists);
-            } else {
-                error!("Device already has a non-WinUSB driver installed: {}", driver);
-                error!("Cannot replace existing driver - manual uninstall required");
-                return Err(WdiError::Exists);
+        // The originals are dropped, having been replaced after the header
+        if in_version && let Some((key, _)) = trimmed.split_once('=') {
+            let key = key.trim();
+            if key.eq_ignore_ascii_case("Class") || key.eq_ignore_ascii_case("ClassGuid") {
+                continue;
             }
         }
-        
-        debug!("Device has no driver installed - proceeding");
-        Ok(())
+        out.push_str(line);
     }
     
-    /// Prepare and install the driver.
-    fn prepare_and_install(mut self, device: Device) -> Result<Device, WdiError> {
-        info!("Preparing and installing driver for device: {}", device);
-        
-        // Determine if we need external INF and set up paths
-        let (driver_path, inf_path, _temp_dir) = match &self.inf_source {
-            InfSource::Embedded { data, filename } => {
-                debug!("Setting up embedded INF file");
-                let temp_dir = TempDir::new()
-                    .map_err(|e| {
-                        error!("Failed to create temporary directory: {}", e);
-                        WdiError::Resource
-                    })?;
-                
-                let driver_path = temp_dir.path().to_str()
-                    .ok_or_else(|| {
-                        error!("Failed to get temporary directory path");
-                        WdiError::InvalidParam
-                    })?
-                    .to_string();
-                
-                let inf_file_path = temp_dir.path().join(filename);
-                debug!("Writing INF file to: {}", inf_file_path.display());
-                
-                fs::write(&inf_file_path, data)
-                    .map_err(|e| {
-                        error!("Failed to write INF file: {}", e);
-                        WdiError::Resource
-                    })?;
-                
-                let inf_path = inf_file_path.to_str()
-                    .ok_or_else(|| {
-                        error!("Failed to convert INF path to string");
-                        WdiError::InvalidParam
-                    })?
-                    .to_string();
-                
-                info!("INF file written successfully");
-                (driver_path, inf_path, Some(temp_dir))
-            }
-            
-            InfSource::External { path } => {
-                debug!("Using external INF file: {}", path.display());
-                
-                if !path.exists() {
-                    error!("External INF file does not exist: {}", path.display());
-                    return Err(WdiError::NotFound);
-                }
-                
-                let driver_path = path.parent()
-                    .ok_or_else(|| {
-                        error!("Invalid external INF path - no parent directory");
-                        WdiError::InvalidParam
-                    })?
-                    .to_str()
-                    .ok_or_else(|| {
-                        error!("Failed to convert driver path to string");
-                        WdiError::InvalidParam
-                    })?
-                    .to_string();
-                
-                let inf_path = path.to_str()
-                    .ok_or_else(|| {
-                        error!("Failed to convert INF path to string");
-                        WdiError::InvalidParam
-                    })?
-                    .to_string();
-                
-                (driver_path, inf_path, None)
-            }
-            
-            InfSource::Generated => {
-                debug!("Using libwdi-generated INF file");
-                let temp_dir = TempDir::new()
-                    .map_err(|e| {
-                        error!("Failed to create temporary directory: {}", e);
-                        WdiError::Resource
-                    })?;
-                
-                let driver_path = temp_dir.path().to_str()
-                    .ok_or_else(|| {
-                        error!("Failed to get temporary directory path");
-                        WdiError::InvalidParam
-                    })?
-                    .to_string();
-                
-                // For generated INF, libwdi will create it
-                let inf_path = format!("{}\\generated.inf", driver_path);
-                
-                (driver_path, inf_path, Some(temp_dir))
+    found.then_some(out)
+}
+
+/// Check that the INF has a `[Version]` section with a valid `Signature`.
+fn check_inf_syntax(data: &[u8]) -> bool {
+    let text = inf_text(data);
+    let mut in_version = false;
+    
+    for line in text.lines() {
+        let line = line.split(';').next().unwrap_or("").trim();
+        if line.starts_with('[') {
+            in_version = line.eq_ignore_ascii_case("[Version]");
+            continue;
+        }
+        if !in_version {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=')
+            && key.trim().eq_ignore_ascii_case("Signature")
+        {
+            let value = value.trim().trim_matches('"');
+            return value.eq_ignore_ascii_case("$Windows NT$") || value.eq_ignore_ascii_case("$Chicago$");
+        }
+    }
+    
+    error!("INF file has no [Version] section Signature");
+    false
+}
+
+/// Check that INF file contents are valid text with a `[Version]` section
+/// `Signature`, logging what is wrong with `name` if not.
+fn check_inf_data(data: &[u8], name: &str) -> Result<(), WdiError> {
+    let valid_text = match data.strip_prefix(&[0xFF, 0xFE]) {
+        Some(body) => body.len() % 2 == 0
+            && char::decode_utf16(body.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])))
+                .all(|c| c.is_ok()),
+        // NULs suggest UTF-16 without a byte order mark, or a binary file
+        None => !data.contains(&0),
+    };
+    if !valid_text {
+        error!("INF file {} is not valid UTF-16LE (with byte order mark) or UTF-8/ANSI text", name);
+        return Err(WdiError::InfSyntax);
+    }
+    
+    if !check_inf_syntax(data) {
+        error!("INF file {} must contain a [Version] section with Signature = \"$Windows NT$\"", name);
+        return Err(WdiError::InfSyntax);
+    }
+    
+    Ok(())
+}
+
+/// Check that the INF's `[Manufacturer]` section has a models section decorated
+/// for the host architecture.
+///
+/// Windows ignores undecorated or foreign-architecture models sections on 64-bit
+/// hosts, which otherwise surfaces as an obscure failure from libwdi.
+fn check_inf_architecture(data: &[u8]) -> Result<(), WdiError> {
+    let text = inf_text(data);
+    let host_arch = host_inf_arch();
+    let mut in_manufacturer = false;
+    let mut found_section = false;
+    let mut decorations = Vec::new();
+
+    for line in text.lines() {
+        let line = line.split(';').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            in_manufacturer = line.eq_ignore_ascii_case("[Manufacturer]");
+            found_section |= in_manufacturer;
+            continue;
+        }
+        if !in_manufacturer {
+            continue;
+        }
+        if let Some((_, models)) = line.split_once('=') {
+            decorations.extend(models.split(',')
+                .skip(1)
+                .map(|d| d.trim().to_ascii_lowercase()));
+        }
+    }
+
+    if !found_section {
+        error!("INF file has no [Manufacturer] section");
+        return Err(WdiError::InvalidParam);
+    }
+
+    // Decorations are NT<arch>[.<major>[.<minor>...]]
+    let matched = decorations.iter().any(|d| {
+        d.strip_prefix(host_arch)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    });
+
+    if !matched {
+        error!("INF file has no [Manufacturer] models section for this architecture ({}) - found: {:?}",
+               host_arch, decorations);
+        return Err(WdiError::InvalidParam);
+    }
+
+    trace!("INF file has a models section for {}", host_arch);
+    Ok(())
+}
+
+/// `[Strings]` keys which hold the manufacturer name, across libwdi versions
+/// and common INF conventions.
+const INF_MANUFACTURER_KEYS: &[&str] = &["ManufacturerName", "VendorName", "MfgName"];
+
+/// `[Strings]` keys which hold the device description.
+const INF_DESCRIPTION_KEYS: &[&str] = &["DeviceName", "DeviceDesc", "DeviceDescription"];
+
+/// Replace the manufacturer and/or device description values in any
+/// `[Strings]` sections of an INF, returning the new text and the number of
+/// values replaced.
+///
+/// Matching is deliberately tolerant - section and key names are compared
+/// case-insensitively, and localized `[Strings.<langid>]` sections are also
+/// rewritten.
+fn rewrite_inf_strings(
+    text: &str,
+    manufacturer: Option<&str>,
+    description: Option<&str>,
+) -> (String, usize) {
+    let mut out = String::with_capacity(text.len());
+    let mut in_strings = false;
+    let mut replaced = 0;
+
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            let section = trimmed.trim_start_matches('[').trim_end_matches(']').to_ascii_lowercase();
+            in_strings = section == "strings" || section.starts_with("strings.");
+        } else if in_strings && let Some((key, _)) = trimmed.split_once('=') {
+            let key = key.trim();
+            let value = if INF_MANUFACTURER_KEYS.iter().any(|k| k.eq_ignore_ascii_case(key)) {
+                manufacturer
+            } else if INF_DESCRIPTION_KEYS.iter().any(|k| k.eq_ignore_ascii_case(key)) {
+                description
+            } else {
+                None
+            };
+
+            if let Some(value) = value {
+                let eol = &line[line.trim_end().len()..];
+                out.push_str(&format!("{} = {}{}", key, inf_quote(value), eol));
+                replaced += 1;
+                continue;
             }
-        };
-        
-        // Set external_inf based on INF source, warning if user tried to set it
-        let should_use_external_inf = !matches!(self.inf_source, InfSource::Generated);
-        
-        if self.options.prepare_opts.external_inf != should_use_external_inf {
-            warn!("Overriding prepare_opts.external_inf (was {}, setting to {}) based on InF source",
-                  self.options.prepare_opts.external_inf, should_use_external_inf);
         }
-        
-        self.options.prepare_opts.external_inf = should_use_external_inf;
-        self.options.prepare_opts.driver_type = self.driver_type;
-        
-        // Prepare the driver
-        debug!("Preparing driver in: {}", driver_path);
-        debug!("INF path: {}", inf_path);
-        
-        prepare_driver(
-            &device,
-            &driver_path,
-            &inf_path,
-            &self.options.prepare_opts,
-        ).map_err(|e| {
-            error!("Failed to prepare driver: {}", e);
-            e
-        })?;
-        
-        info!("Driver prepared successfully");
-        
-        // Install the driver
-        debug!("Installing driver");
-        
-        install_driver(
-            &device,
-            &driver_path,
-            &inf_path,
-            &self.options.install_opts,
-        ).map_err(|e| {
-            error!("Failed to install driver: {}", e);
-            e
-        })?;
-        
-        info!("Driver installed successfully");
-        
-        // Keep temp_dir alive until here so it doesn't get cleaned up prematurely
-        drop(_temp_dir);
-        
-        Ok(device)
+        out.push_str(line);
     }
+
+    (out, replaced)
 }
 
 impl fmt::Debug for DriverInstaller {
@@ -650,7 +4087,36 @@ impl fmt::Debug for DriverInstaller {
         f.debug_struct("DriverInstaller")
             .field("device_selector", &self.device_selector)
             .field("driver_type", &self.driver_type)
+            .field("vendor_name", &self.vendor_name)
+            .field("device_description", &self.device_description)
             .field("inf_source", &self.inf_source)
+            .field("options", &self.options)
+            .field("verify_present", &self.verify_present)
+            .field("best_effort", &self.best_effort)
+            .field("ui_language", &self.ui_language)
+            .field("interface_registration", &self.interface_registration)
+            .field("ready_timeout", &self.ready_timeout)
+            .field("chooser", &self.chooser.as_ref().map(|_| "<chooser>"))
+            .field("on_installed", &self.on_installed.as_ref().map(|_| "<callback>"))
+            .field("progress", &self.progress.as_ref().map(|_| "<callback>"))
+            .field("temp_prefix", &self.temp_prefix)
+            .field("temp_dir", &self.temp_dir)
+            .field("keep_files", &self.keep_files)
+            .field("vendor_lookup", &self.vendor_lookup)
+            .field("force_replace", &self.force_replace)
+            .field("only_if_unknown", &self.only_if_unknown)
+            .field("coinstaller", &self.coinstaller.as_ref()
+                .map(|((major, minor), data)| format!("{}.{} ({} bytes)", major, minor, data.len())))
+            .field("backup_dir", &self.backup_dir)
+            .field("retry", &self.retry)
+            .field("device_class", &self.device_class)
+            .field("device_guid", &self.device_guid)
+            .field("wcid", &self.wcid)
+            .field("replug", &self.replug.as_ref().map(|(timeout, _)| timeout))
+            .field("install_mode", &self.install_mode)
+            .field("additional_files", &self.additional_files.iter()
+                .map(|(path, _)| path)
+                .collect::<Vec<_>>())
             .finish()
     }
 }
@@ -658,6 +4124,7 @@ impl fmt::Debug for DriverInstaller {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::wdi::test_device;
     
     #[test]
     fn test_device_selector_vid_pid() {
@@ -671,16 +4138,374 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_device_selector_index() {
+        let installer = DriverInstaller::for_index(3);
+        assert!(matches!(installer.device_selector, DeviceSelector::Index(3)));
+    }
+    
+    #[test]
+    fn test_device_selector_serial() {
+        let installer = DriverInstaller::for_serial(0x1234, 0x5678, "ABC123");
+        assert_eq!(format!("{:?}", installer.device_selector), "Serial(1234:5678, ABC123)");
+        
+        let device = Device {
+            device_id: Some("USB\\VID_1234&PID_5678\\ABC123".to_string()),
+            ..test_device(0x1234, 0x5678)
+        };
+        assert!(serial_matches(&device, "abc123"));
+        assert!(!serial_matches(&device, "C12"));
+        assert!(!serial_matches(&device, "XYZ"));
+        
+        // Serials which appear in the VID and PID don't match
+        assert!(!serial_matches(&device, "1234"));
+        assert!(!serial_matches(&device, "5678"));
+        assert!(!serial_matches(&device, "VID"));
+    }
+    
+    #[test]
+    fn test_device_selector_interface() {
+        let installer = DriverInstaller::for_interface(0x1234, 0x5678, 2);
+        assert_eq!(format!("{:?}", installer.device_selector), "VidPidMi(1234:5678, 2)");
+    }
+    
+    #[test]
+    fn test_device_selector_friendly_name() {
+        let installer = DriverInstaller::for_friendly_name("ACME Widget");
+        assert_eq!(format!("{:?}", installer.device_selector), "FriendlyName(ACME Widget)");
+    }
+    
+    #[test]
+    fn test_device_selector_any_vid_pid() {
+        let installer = DriverInstaller::for_any_vid_pid(vec![(0x1234, 0x5678), (0xabcd, 0x0001)]);
+        assert_eq!(format!("{:?}", installer.device_selector), "AnyVidPid(1234:5678, abcd:0001)");
+    }
+    
     #[test]
     fn test_builder_pattern() {
         let installer = DriverInstaller::for_device(0x1234, 0x5678)
             .with_driver_type(DriverType::LibUsb0)
             .with_inf_data(b"test data", "test.inf");
         
-        assert!(matches!(installer.driver_type, DriverType::LibUsb0));
+        assert!(matches!(installer.driver_type, Some(DriverType::LibUsb0)));
         assert!(matches!(installer.inf_source, InfSource::Embedded { .. }));
     }
     
+    #[test]
+    fn test_install_mode_filter() {
+        let device = Device { driver: Some("acmeusb".to_string()), ..test_device(0x1234, 0x5678) };
+        
+        let installer = DriverInstaller::for_device(0x1234, 0x5678)
+            .with_driver_type(DriverType::LibUsb0);
+        assert!(matches!(installer.check_existing_driver(&device), Err(WdiError::ConflictingDriver { .. })));
+        
+        let mut installer = installer.install_mode(InstallMode::Filter);
+        assert!(installer.check_existing_driver(&device).is_ok());
+        installer.apply_builder_overrides();
+        assert!(installer.options.install_opts.install_filter_driver);
+        
+        let filtered = Device { upper_filter: Some("libusb0".to_string()), ..device };
+        assert!(matches!(installer.check_existing_driver(&filtered), Err(WdiError::AlreadyInstalled)));
+    }
+    
+    #[test]
+    fn test_install_each_continues_after_failure() {
+        let devices = vec![test_device(0x1234, 0x0001), test_device(0x1234, 0x0002), test_device(0x1234, 0x0003)];
+        let mut attempted = Vec::new();
+        let results = install_each(devices, |device| {
+            attempted.push(device.pid);
+            if device.pid == 0x0002 { Err(WdiError::Busy) } else { Ok(()) }
+        });
+        
+        assert_eq!(attempted, [0x0001, 0x0002, 0x0003]);
+        let pids: Vec<_> = results.iter().map(|(device, _)| device.pid).collect();
+        assert_eq!(pids, [0x0001, 0x0002, 0x0003]);
+        assert!(results[0].1.is_ok());
+        assert!(matches!(results[1].1, Err(WdiError::Busy)));
+        assert!(results[2].1.is_ok());
+    }
+    
+    #[test]
+    fn test_only_if_unknown() {
+        let device = Device { driver: Some("acmeusb".to_string()), ..test_device(0x1234, 0x5678) };
+        let installer = DriverInstaller::for_device(0x1234, 0x5678)
+            .with_driver_type(DriverType::WinUsb)
+            .force_replace(true)
+            .only_if_unknown();
+        
+        // A working driver is left alone, even when replacement is forced
+        assert!(matches!(installer.check_existing_driver_with(&device, |_| true), Err(WdiError::Exists)));
+        assert!(installer.check_existing_driver_with(&device, |_| false).is_ok());
+        
+        // Driverless devices, and those on a generic driver, aren't functional
+        let driverless = test_device(0x1234, 0x5678);
+        assert!(installer.check_existing_driver(&driverless).is_ok());
+        let generic = Device { driver: Some("usbccgp".to_string()), ..device };
+        assert!(installer.check_existing_driver(&generic).is_ok());
+        
+        let installer = DriverInstaller::for_device(0x1234, 0x5678).install_mode(InstallMode::Filter);
+        assert!(matches!(installer.check_driver_supported(), Err(WdiError::NotSupported)));
+    }
+    
+    #[test]
+    fn test_installer_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<DriverInstaller>();
+    }
+    
+    #[test]
+    fn test_debug_includes_settings() {
+        let installer = DriverInstaller::for_device(0x1234, 0x5678)
+            .with_vendor_name("ACME")
+            .with_device_description("Widget");
+        
+        let debug = format!("{:?}", installer);
+        assert!(debug.contains("vendor_name: Some(\"ACME\")"));
+        assert!(debug.contains("device_description: Some(\"Widget\")"));
+        assert!(debug.contains("options: InstallOptions"));
+    }
+    
+    #[test]
+    fn test_progress_callback() {
+        use std::sync::{Arc, Mutex};
+        
+        let phases = Arc::new(Mutex::new(Vec::new()));
+        let recorded = phases.clone();
+        let installer = DriverInstaller::for_device(0x1234, 0x5678)
+            .with_progress(Box::new(move |phase| recorded.lock().unwrap().push(phase)));
+        
+        installer.report(InstallPhase::Enumerating);
+        installer.report(InstallPhase::DeviceFound);
+        assert_eq!(*phases.lock().unwrap(), [InstallPhase::Enumerating, InstallPhase::DeviceFound]);
+    }
+    
+    #[test]
+    fn test_worker_skips_enumerating() {
+        use std::sync::{Arc, Mutex};
+        
+        let phases = Arc::new(Mutex::new(Vec::new()));
+        let recorded = phases.clone();
+        let installer = DriverInstaller::for_device(0x1234, 0x5678)
+            .with_progress(Box::new(move |phase| recorded.lock().unwrap().push(phase)));
+        
+        installer.report_enumerating();
+        let worker = installer.into_worker(test_device(0x1234, 0x5678));
+        worker.report_enumerating();
+        assert_eq!(*phases.lock().unwrap(), [InstallPhase::Enumerating]);
+    }
+    
+    #[test]
+    fn test_prepare_options_precedence() {
+        let opts = PrepareDriverOptions {
+            driver_type: DriverType::LibUsbK,
+            vendor_name: Some("From options".to_string()),
+            disable_signing: true,
+            ..PrepareDriverOptions::default()
+        };
+
+        // Options alone are preserved
+        let mut installer = DriverInstaller::for_device(0x1234, 0x5678)
+            .with_prepare_options(opts.clone());
+        installer.apply_builder_overrides();
+        assert!(matches!(installer.options.prepare_opts.driver_type, DriverType::LibUsbK));
+        assert_eq!(installer.options.prepare_opts.vendor_name.as_deref(), Some("From options"));
+
+        // Explicit builder calls win, whatever the call order
+        let mut installer = DriverInstaller::for_device(0x1234, 0x5678)
+            .with_driver_type(DriverType::Cdc)
+            .with_device_guid("{6e45736a-2b1b-4078-b772-b3af2b6fde1c}".parse().unwrap())
+            .with_prepare_options(opts)
+            .with_vendor_name("From builder");
+        installer.apply_builder_overrides();
+        assert!(matches!(installer.options.prepare_opts.driver_type, DriverType::Cdc));
+        assert_eq!(installer.options.prepare_opts.vendor_name.as_deref(), Some("From builder"));
+        assert_eq!(
+            installer.options.prepare_opts.device_guid.as_deref(),
+            Some("{6E45736A-2B1B-4078-B772-B3AF2B6FDE1C}"),
+        );
+        assert!(!installer.options.prepare_opts.use_wcid_driver);
+        
+        let mut installer = installer.with_wcid(true);
+        installer.apply_builder_overrides();
+        assert!(installer.options.prepare_opts.use_wcid_driver);
+        assert!(installer.options.prepare_opts.disable_signing);
+        assert!(!installer.is_unsigned_install());
+        
+        let installer = DriverInstaller::for_device(0x1234, 0x5678)
+            .with_prepare_options(PrepareDriverOptions {
+                disable_cat: true,
+                disable_signing: true,
+                ..PrepareDriverOptions::default()
+            });
+        assert!(installer.is_unsigned_install());
+    }
+    
+    #[test]
+    fn test_rewrite_inf_strings() {
+        let inf = "[Strings]\r\nDeviceName = \"USB Device\"\r\nVendorName = \"Generic\"\r\nDeviceID = \"VID_1234\"\r\n\
+                   [Version]\r\nProvider = %VendorName%\r\n";
+        let (text, replaced) = rewrite_inf_strings(inf, Some("ACME \"Corp\""), Some("Widget"));
+        assert_eq!(replaced, 2);
+        assert!(text.contains("DeviceName = \"Widget\"\r\n"));
+        assert!(text.contains("VendorName = \"ACME \"\"Corp\"\"\"\r\n"));
+        assert!(text.contains("DeviceID = \"VID_1234\"\r\n"));
+        assert!(text.contains("Provider = %VendorName%\r\n"));
+        
+        // As generated by libwdi from a device description with special characters
+        let generated = "[Strings]\nDeviceName = \"My \"Special\" 100% Device\"\n";
+        let (text, replaced) = rewrite_inf_strings(generated, None, Some("My \"Special\" 100% Device"));
+        assert_eq!(replaced, 1);
+        assert_eq!(text, "[Strings]\nDeviceName = \"My \"\"Special\"\" 100%% Device\"\n");
+
+        let (text, replaced) = rewrite_inf_strings("[Version]\nVendorName = x\n", Some("ACME"), None);
+        assert_eq!(replaced, 0);
+        assert_eq!(text, "[Version]\nVendorName = x\n");
+    }
+    
+    #[test]
+    fn test_inf_architecture() {
+        let sample = include_bytes!("../inf/sample.inf");
+        assert!(check_inf_architecture(sample).is_ok());
+
+        let other = format!("[Manufacturer]\n%Vendor% = Models,{}\n",
+            if host_inf_arch() == "ntx86" { "NTamd64" } else { "NTx86" });
+        assert!(check_inf_architecture(other.as_bytes()).is_err());
+
+        let versioned = format!("[Manufacturer]\n%Vendor% = Models,{}.10.0\n", host_inf_arch());
+        assert!(check_inf_architecture(versioned.as_bytes()).is_ok());
+
+        assert!(check_inf_architecture(b"[Version]\nSignature = \"$Windows NT$\"\n").is_err());
+    }
+    
+    #[test]
+    fn test_parse_driver_ver() {
+        let inf = "[Version]\nDriverVer = 01/15/2024, 1.2.3.4 ; comment\n";
+        assert_eq!(parse_driver_ver(inf), Some(([1, 2, 3, 4], (2024, 1, 15))));
+        assert_eq!(parse_driver_ver("DriverVer=12/31/2006"), Some(([0; 4], (2006, 12, 31))));
+        assert_eq!(parse_driver_ver("DriverVer = #DRIVER_DATE#, #DRIVER_VERSION#"), None);
+        assert!(parse_driver_ver("[Version]\n").is_none());
+        assert!(([2, 0, 0, 0], (2020, 1, 1)) > ([1, 9, 0, 0], (2024, 1, 1)));
+    }
+    
+    #[test]
+    fn test_inf_matches_device() {
+        let mut device = test_device(0x1234, 0xabcd);
+        assert!(inf_matches_device("%D% = Install, USB\\VID_1234&PID_ABCD\n", &device));
+        assert!(!inf_matches_device("%D% = Install, USB\\VID_1234&PID_ABCD&MI_01\n", &device));
+        assert!(!inf_matches_device("%D% = Install, USB\\VID_1234&PID_ABCE\n", &device));
+        
+        device.is_composite = true;
+        device.mi = 1;
+        assert!(inf_matches_device("%D% = Install, usb\\vid_1234&pid_abcd&mi_01\n", &device));
+        assert!(!inf_matches_device("%D% = Install, USB\\VID_1234&PID_ABCD&MI_02\n", &device));
+    }
+    
+    #[test]
+    fn test_check_driver_kind() {
+        let device = Device { driver: Some("WinUSB".to_string()), ..test_device(0x1234, 0x5678) };
+        assert!(check_driver_kind(&device, DriverType::WinUsb).is_ok());
+        assert!(check_driver_kind(&device, DriverType::User).is_ok());
+        assert!(matches!(
+            check_driver_kind(&device, DriverType::LibUsbK),
+            Err(WdiError::ConflictingDriver { current }) if current == "WinUSB"
+        ));
+    }
+    
+    #[test]
+    fn test_check_staged_paths() {
+        let file = |path: &str| (PathBuf::from(path), Vec::new());
+        
+        assert!(check_staged_paths("widget.inf", &[file("amd64/widget.sys"), file("widget.cat")]).is_ok());
+        assert!(check_staged_paths("widget.inf", &[file("amd64/widget.sys"), file("AMD64/Widget.SYS")]).is_err());
+        assert!(check_staged_paths("widget.inf", &[file("./WIDGET.INF")]).is_err());
+        assert!(check_staged_paths("widget.inf", &[file("../widget.sys")]).is_err());
+        assert!(check_staged_paths("widget.inf", &[file("")]).is_err());
+    }
+    
+    #[test]
+    fn test_temp_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let installer = DriverInstaller::for_device(0x1234, 0x5678)
+            .with_temp_prefix("wdi-test-")
+            .with_temp_dir(dir.path().to_path_buf());
+        assert!(installer.check_temp_dir().is_ok());
+        let staged = installer.create_temp_dir().unwrap();
+        assert_eq!(staged.path().parent(), Some(dir.path()));
+        
+        let installer = installer.with_temp_dir(dir.path().join("missing"));
+        assert!(matches!(installer.check_temp_dir(), Err(WdiError::Access)));
+    }
+    
+    #[test]
+    fn test_copy_dir() {
+        let src = tempfile::tempdir().unwrap();
+        fs::create_dir(src.path().join("amd64")).unwrap();
+        fs::write(src.path().join("widget.inf"), "[Version]\n").unwrap();
+        fs::write(src.path().join("amd64").join("widget.sys"), [0u8; 4]).unwrap();
+        
+        let dest = tempfile::tempdir().unwrap();
+        let backup = dest.path().join("widget.inf_amd64_0123");
+        copy_dir(src.path(), &backup).unwrap();
+        assert_eq!(fs::read_to_string(backup.join("widget.inf")).unwrap(), "[Version]\n");
+        assert_eq!(fs::read(backup.join("amd64").join("widget.sys")).unwrap(), [0u8; 4]);
+    }
+    
+    #[test]
+    fn test_rewrite_inf_class() {
+        let guid: DeviceInterfaceGuid = "{6e45736a-2b1b-4078-b772-b3af2b6fde1c}".parse().unwrap();
+        let inf = "[Version]\r\nSignature = \"$Windows NT$\"\r\nClass = USBDevice\r\n\
+                   ClassGUID = {88BAE032-5A81-49f0-BC3D-A4FF138216D6}\r\n\r\n[Strings]\r\nClass = \"x\"\r\n";
+        let text = rewrite_inf_class(inf, "AcmeWidgets", guid).unwrap();
+        assert_eq!(
+            text,
+            "[Version]\r\nClass = AcmeWidgets\r\nClassGuid = {6E45736A-2B1B-4078-B772-B3AF2B6FDE1C}\r\n\
+             Signature = \"$Windows NT$\"\r\n\r\n[Strings]\r\nClass = \"x\"\r\n",
+        );
+        
+        assert!(rewrite_inf_class("[Strings]\n", "AcmeWidgets", guid).is_none());
+        assert!(is_valid_class_name("AcmeWidgets"));
+        assert!(!is_valid_class_name(" "));
+        assert!(!is_valid_class_name("Acme;Widgets"));
+    }
+    
+    #[test]
+    fn test_patch_coinstaller() {
+        assert_eq!(coinstaller_filename((1, 11)), "WdfCoInstaller01011.dll");
+        
+        let inf = "[CoInst_AddReg]\nHKR,,CoInstallers32,0x10000,\"WdfCoInstaller01009.dll,WdfCoInstaller\"\n\
+                   [CoInst_CopyFiles]\nwdfcoinstaller01009.dll\n\
+                   [Wdf]\nKmdfLibraryVersion = 1.9\n";
+        let (patched, replaced) = patch_coinstaller(inf, (1, 11));
+        assert_eq!(replaced, 3);
+        assert_eq!(patched, "[CoInst_AddReg]\nHKR,,CoInstallers32,0x10000,\"WdfCoInstaller01011.dll,WdfCoInstaller\"\n\
+                             [CoInst_CopyFiles]\nWdfCoInstaller01011.dll\n\
+                             [Wdf]\nKmdfLibraryVersion = 1.11\n");
+        
+        let (unchanged, replaced) = patch_coinstaller("[Version]\n", (1, 11));
+        assert_eq!((unchanged.as_str(), replaced), ("[Version]\n", 0));
+        
+        // Multibyte characters where the name would end don't panic
+        let (unchanged, replaced) = patch_coinstaller("WdfCoInstaller01011.dlé", (1, 11));
+        assert_eq!((unchanged.as_str(), replaced), ("WdfCoInstaller01011.dlé", 0));
+    }
+    
+    #[test]
+    fn test_inf_syntax() {
+        assert!(check_inf_syntax(include_bytes!("../inf/sample.inf")));
+        assert!(check_inf_syntax(b"[Version]\nSignature = \"$Windows NT$\" ; comment\n"));
+        assert!(!check_inf_syntax(b"[Version]\nSignature = \"$Unknown$\"\n"));
+        assert!(!check_inf_syntax(b"[Strings]\nSignature = \"$Windows NT$\"\n"));
+        
+        assert!(check_inf_data(include_bytes!("../inf/sample.inf"), "sample.inf").is_ok());
+        let utf16: Vec<u8> = [0xFF, 0xFE].into_iter()
+            .chain("[Version]\nSignature = \"$Windows NT$\"\n".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        assert!(check_inf_data(&utf16, "utf16.inf").is_ok());
+        assert!(matches!(check_inf_data(&utf16[2..], "no_bom.inf"), Err(WdiError::InfSyntax)));
+        assert!(matches!(check_inf_data(&utf16[..utf16.len() - 1], "odd.inf"), Err(WdiError::InfSyntax)));
+        assert!(matches!(check_inf_data(b"[Strings]\n", "empty.inf"), Err(WdiError::InfSyntax)));
+    }
+    
     #[test]
     fn test_default_inf_source() {
         let installer = DriverInstaller::for_device(0x1234, 0x5678);
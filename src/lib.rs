@@ -136,10 +136,14 @@
 //!             info!("Successfully installed driver for: {}", device);
 //!             Ok(())
 //!         }
-//!         Err(Error::Exists) => {
+//!         Err(Error::AlreadyInstalled) => {
 //!             info!("Driver already installed");
 //!             Ok(())
 //!         }
+//!         Err(Error::ConflictingDriver { current }) => {
+//!             error!("Device already has a conflicting driver: {}", current);
+//!             Err(Error::ConflictingDriver { current })
+//!         }
 //!         Err(Error::NotFound) => {
 //!             error!("Device {:04x}:{:04x} not found", vid, pid);
 //!             Err(Error::NotFound)
@@ -222,6 +226,21 @@
 //! - Error propagation
 //! - Safe cleanup
 //!
+//! ### Batch operations
+//!
+//! libwdi has no context to set up or tear down between calls.  Its one-time
+//! setup, loading the Windows libraries it uses, happens on first use and is
+//! kept for the life of the process, so no `WdiContext`-style type is needed
+//! to amortize it - the free functions are already as cheap to call
+//! repeatedly as they can be.  The per-operation costs are enumeration,
+//! which queries every USB device, and installation itself, which is
+//! dominated by Windows.
+//!
+//! Tools installing many drivers should therefore enumerate once and reuse
+//! the result, for example with [`install_targets_typed`], or by passing
+//! devices from one [`create_list`] call to
+//! [`DriverInstaller::for_specific_device`].
+//!
 //! ## Common Use Cases
 //!
 //! ### Command-line tool for driver installation
@@ -305,6 +324,10 @@
 //! }
 //! ```
 //!
+//! For unattended installers, [`enable_ring_log`] captures the crate's recent log messages at all levels in memory, regardless of the configured log level.  Use [`dump_ring_log`] to retrieve them, for example to attach to a crash report after an installation failure.
+//!
+//! libwdi's own log messages can also be forwarded to the `log` crate with [`capture_libwdi_logs`], or read directly with a [`LogReader`].
+//!
 //! ## Safety
 //!
 //! This crate uses unsafe code to interface with the libwdi C library. All unsafe code is carefully reviewed and encapsulated behind safe APIs. The high-level `DriverInstaller` API is entirely safe Rust.
@@ -330,18 +353,56 @@
 #[cfg(any(target_os = "windows", doc))]
 mod ffi;
 #[cfg(any(target_os = "windows", doc))]
+mod inf;
+#[cfg(any(target_os = "windows", doc))]
 mod installer;
 #[cfg(any(target_os = "windows", doc))]
+mod logger;
+#[cfg(all(any(target_os = "windows", doc), feature = "serde"))]
+mod manifest;
+#[cfg(any(target_os = "windows", doc))]
+mod monitor;
+#[cfg(all(any(target_os = "windows", doc), feature = "serde"))]
+mod record;
+#[cfg(any(target_os = "windows", doc))]
+mod ring_log;
+#[cfg(any(target_os = "windows", doc))]
+mod setupapi;
+#[cfg(any(target_os = "windows", doc))]
+mod system;
+#[cfg(any(target_os = "windows", doc))]
 mod wdi;
+#[cfg(any(target_os = "windows", doc))]
+mod wintrust;
 
 #[cfg(any(target_os = "windows", doc))]
-pub use installer::{DriverInstaller, DeviceSelector, InfSource, InstallOptions};
+pub use inf::{generate_winusb_inf, WinUsbInfParams};
+#[cfg(any(target_os = "windows", doc))]
+pub use installer::{DriverInstaller, DeviceSelector, DeviceTarget, InfSource, InfSelection, install_targets_typed, resolve_targets, InstallMode, InstallOptions, InstallOutcome, InstallPhase, InstallPreview, InstallHandle, PackageValidation, CompositeInstallReport, DeviceChoice, DeviceResults};
+#[cfg(any(target_os = "windows", doc))]
+pub use logger::{capture_libwdi_logs, capture_libwdi_logs_with_buffer, stop_capturing_libwdi_logs, LogReader, DEFAULT_LIBWDI_LOG_BUFFER_SIZE};
+#[cfg(all(any(target_os = "windows", doc), feature = "serde"))]
+pub use manifest::{install_from_manifest, ManifestResults};
+#[cfg(any(target_os = "windows", doc))]
+pub use monitor::{DeviceDiff, DeviceMonitor};
+#[cfg(all(any(target_os = "windows", doc), feature = "serde"))]
+pub use record::{read_install_records, InstallRecord};
+#[cfg(any(target_os = "windows", doc))]
+pub use ring_log::{enable_ring_log, dump_ring_log};
+#[cfg(any(target_os = "windows", doc))]
+pub use setupapi::{get_device_interface_path, uninstall_oem_inf};
+#[cfg(any(target_os = "windows", doc))]
+pub use system::{is_reboot_pending, is_installation_pending, clear_pending_installation};
 #[cfg(any(target_os = "windows", doc))]
 pub use wdi::{
-    create_list, prepare_driver, install_driver,
-    CreateListOptions, Device, DeviceList, PrepareDriverOptions, InstallDriverOptions,
-    DriverType, Error, set_log_level,
+    create_list, create_list_cancelable, create_list_for_each, create_list_with_timeout, list_winusb_devices, prepare_driver, install_driver, install_trusted_certificate, is_driver_supported, is_file_embedded, wdf_version,
+    CreateListOptions, CompositeDetail, Device, DeviceInterfaceGuid, DeviceKey, DeviceList, PickerEntry, PrepareDriverOptions, InstallDriverOptions,
+    DriverType, DriverKind, DriverVersion, Error, WindowHandle, set_log_level, ConcurrencyPolicy, set_concurrency_policy,
 };
+#[cfg(all(any(target_os = "windows", doc), feature = "serde"))]
+pub use wdi::list_devices_json;
+#[cfg(any(target_os = "windows", doc))]
+pub use wintrust::SignatureStatus;
 
 #[cfg(all(not(target_os = "windows"), not(doc)))]
 compile_error!("This crate only supports Windows");
\ No newline at end of file
@@ -0,0 +1,247 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Capture of libwdi's own log messages, either read directly with a
+//! [`LogReader`] or forwarded to the `log` crate.
+//!
+//! libwdi writes its log messages to an internal pipe, from which they are
+//! read with `wdi_read_logger`.  When capturing, a background thread polls
+//! the pipe and forwards each message through this crate's logging, so they
+//! also appear in the ring buffer if enabled.
+
+use crate::ffi::{wdi_register_logger, wdi_unregister_logger, wdi_read_logger};
+use crate::Error;
+use crate::ring_log::{debug, error, ring_log};
+use std::ffi::c_char;
+use std::fmt;
+use std::ptr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// The default size of libwdi's log buffer, in bytes, used by
+/// [`capture_libwdi_logs`] and [`LogReader::new`].
+pub const DEFAULT_LIBWDI_LOG_BUFFER_SIZE: u32 = 64 * 1024;
+
+/// How often the capture thread checks for new messages.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The largest message libwdi logs, including the terminating NUL.
+const MAX_MESSAGE_SIZE: usize = 4096;
+
+/// Reads libwdi's own log messages, such as its progress during an
+/// installation, for example to show to users.
+///
+/// Only one reader can exist at a time, including the one used by
+/// [`capture_libwdi_logs`].  It is unregistered from libwdi when dropped.
+///
+/// # Examples
+///
+/// ```no_run
+/// use wdi_rs::{DriverInstaller, LogReader};
+///
+/// let mut reader = LogReader::new()?;
+/// let _handle = DriverInstaller::for_device(0x1234, 0x5678).start_install();
+///
+/// // ...then, periodically while the installation runs
+/// while let Some(line) = reader.read_line() {
+///     println!("{}", line);
+/// }
+/// # Ok::<(), wdi_rs::Error>(())
+/// ```
+pub struct LogReader {
+    buffer: Vec<u8>,
+}
+
+impl LogReader {
+    /// Registers a reader for libwdi's log messages, with a log buffer of
+    /// [`DEFAULT_LIBWDI_LOG_BUFFER_SIZE`] bytes.
+    ///
+    /// # Errors
+    /// * Returns `Error::Exists` if a reader already exists.
+    pub fn new() -> Result<Self, Error> {
+        Self::with_buffer(DEFAULT_LIBWDI_LOG_BUFFER_SIZE)
+    }
+
+    /// Registers a reader for libwdi's log messages, with a log buffer of
+    /// `size` bytes.
+    ///
+    /// libwdi queues its messages in the buffer until they are read.  If the
+    /// buffer fills, further messages are dropped until there is space again,
+    /// so read regularly, or increase `size`, to avoid losing lines.
+    ///
+    /// # Errors
+    /// * Returns `Error::Exists` if a reader already exists.
+    /// * Returns any other error from registering with libwdi.
+    pub fn with_buffer(size: u32) -> Result<Self, Error> {
+        debug!("Registering libwdi logger with a {} byte buffer", size);
+        Error::from_code(unsafe { wdi_register_logger(ptr::null_mut(), 0, size) }).inspect_err(|e| {
+            error!("Failed to register libwdi logger: {}", e);
+        })?;
+
+        Ok(LogReader { buffer: vec![0u8; MAX_MESSAGE_SIZE] })
+    }
+
+    /// Reads the next queued log message, without its trailing newline.
+    ///
+    /// Returns `None` if no message is queued - this doesn't wait for one.
+    pub fn read_line(&mut self) -> Option<String> {
+        let mut size = 0;
+        let result = unsafe {
+            wdi_read_logger(self.buffer.as_mut_ptr() as *mut c_char, self.buffer.len() as u32, &mut size)
+        };
+        if result != 0 || size == 0 {
+            return None;
+        }
+
+        let message = &self.buffer[..(size as usize).min(self.buffer.len())];
+        let len = message.iter().position(|&b| b == 0).unwrap_or(message.len());
+        Some(String::from_utf8_lossy(&message[..len]).trim_end().to_string())
+    }
+}
+
+impl fmt::Debug for LogReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LogReader")
+            .field("buffer_len", &self.buffer.len())
+            .finish()
+    }
+}
+
+impl Drop for LogReader {
+    fn drop(&mut self) {
+        unsafe {
+            wdi_unregister_logger(ptr::null_mut());
+        }
+        debug!("Unregistered libwdi logger");
+    }
+}
+
+struct Capture {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+static CAPTURE: Mutex<Option<Capture>> = Mutex::new(None);
+
+/// Captures libwdi's own log messages, forwarding them to the `log` crate
+/// under this crate's target, with a log buffer of
+/// [`DEFAULT_LIBWDI_LOG_BUFFER_SIZE`] bytes.
+///
+/// See [`capture_libwdi_logs_with_buffer`].
+pub fn capture_libwdi_logs() -> Result<(), Error> {
+    capture_libwdi_logs_with_buffer(DEFAULT_LIBWDI_LOG_BUFFER_SIZE)
+}
+
+/// Captures libwdi's own log messages, forwarding them to the `log` crate
+/// under this crate's target, with a log buffer of `size` bytes.
+///
+/// libwdi queues its messages in the buffer until they are read, which a
+/// background thread does every few milliseconds.  If the buffer fills, for
+/// example during a burst of verbose debug output, further messages are
+/// dropped until there is space again - so increase `size` to avoid losing
+/// lines when capturing chatty installations.  Which messages libwdi logs is
+/// controlled by [`set_log_level`](crate::set_log_level).
+///
+/// Stop capturing with [`stop_capturing_libwdi_logs`].  To read the messages
+/// directly instead, use a [`LogReader`].
+///
+/// # Errors
+/// * Returns `Error::Exists` if libwdi's logs are already being captured, or
+///   a [`LogReader`] exists.
+/// * Returns any other error from registering with libwdi.
+///
+/// # Examples
+///
+/// ```no_run
+/// use log::LevelFilter;
+/// use wdi_rs::{capture_libwdi_logs_with_buffer, set_log_level};
+///
+/// set_log_level(LevelFilter::Debug.into())?;
+/// capture_libwdi_logs_with_buffer(256 * 1024)?;
+/// # Ok::<(), wdi_rs::Error>(())
+/// ```
+pub fn capture_libwdi_logs_with_buffer(size: u32) -> Result<(), Error> {
+    let mut capture = CAPTURE.lock().unwrap_or_else(|e| e.into_inner());
+    if capture.is_some() {
+        error!("libwdi logs are already being captured");
+        return Err(Error::Exists);
+    }
+
+    debug!("Capturing libwdi logs");
+    let reader = LogReader::with_buffer(size)?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let stop = stop.clone();
+        thread::spawn(move || forward_messages(reader, &stop))
+    };
+    *capture = Some(Capture { stop, thread });
+    Ok(())
+}
+
+/// Stops capturing libwdi's log messages, after forwarding any already
+/// queued.  Does nothing if they aren't being captured.
+pub fn stop_capturing_libwdi_logs() {
+    let mut capture = CAPTURE.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(Capture { stop, thread }) = capture.take() else {
+        return;
+    };
+
+    // The thread drops the reader, unregistering it
+    stop.store(true, Ordering::Relaxed);
+    let _ = thread.join();
+    debug!("Stopped capturing libwdi logs");
+}
+
+/// Forward libwdi's messages until `stop` is set.
+fn forward_messages(mut reader: LogReader, stop: &AtomicBool) {
+    loop {
+        // Check before draining, so messages queued before stopping are kept
+        let stopping = stop.load(Ordering::Relaxed);
+        while let Some(message) = reader.read_line() {
+            let (level, message) = parse_message(&message);
+            ring_log!(level, "libwdi: {}", message);
+        }
+        if stopping {
+            break;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Split libwdi's `libwdi:<level> ` prefix from a message, returning the
+/// level to log it at and the rest of the message.
+fn parse_message(message: &str) -> (log::Level, &str) {
+    let Some((prefix, rest)) = message.strip_prefix("libwdi:")
+        .and_then(|rest| rest.split_once(' '))
+    else {
+        return (log::Level::Info, message);
+    };
+
+    let level = match prefix {
+        "error" => log::Level::Error,
+        "warning" => log::Level::Warn,
+        "debug" => log::Level::Debug,
+        _ => log::Level::Info,
+    };
+    (level, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_message() {
+        assert_eq!(
+            parse_message("libwdi:debug [wdi_prepare_driver] using inf path"),
+            (log::Level::Debug, "[wdi_prepare_driver] using inf path"),
+        );
+        assert_eq!(parse_message("libwdi:warning [x] y"), (log::Level::Warn, "[x] y"));
+        assert_eq!(parse_message("unprefixed"), (log::Level::Info, "unprefixed"));
+    }
+}
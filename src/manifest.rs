@@ -0,0 +1,207 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Installing drivers from a declarative TOML or JSON manifest.
+
+use crate::{Device, DeviceTarget, DriverInstaller, DriverType, Error};
+use crate::installer::install_targets_with;
+use crate::ring_log::{debug, error, info};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Each manifest entry's target, with its result - see
+/// [`install_from_manifest`].
+pub type ManifestResults = Vec<(DeviceTarget, Result<Device, Error>)>;
+
+/// A driver manifest, as read by [`install_from_manifest`].
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(default)]
+    devices: Vec<ManifestEntry>,
+}
+
+/// A single device in a driver manifest.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestEntry {
+    vid: Option<u16>,
+    pid: Option<u16>,
+    mi: Option<u8>,
+    serial: Option<String>,
+    device_id: Option<String>,
+    driver_type: Option<DriverType>,
+    inf: Option<PathBuf>,
+    vendor_name: Option<String>,
+    device_description: Option<String>,
+    #[serde(default)]
+    force_replace: bool,
+}
+
+impl ManifestEntry {
+    /// The target identifying this entry's device.
+    fn target(&self) -> Result<DeviceTarget, Error> {
+        match (&self.device_id, self.vid, self.pid) {
+            (Some(id), None, None) if self.mi.is_none() && self.serial.is_none() => {
+                Ok(DeviceTarget::DeviceId(id.clone()))
+            }
+            (None, Some(vid), Some(pid)) => Ok(DeviceTarget::Usb {
+                vid,
+                pid,
+                mi: self.mi,
+                serial: self.serial.clone(),
+            }),
+            _ => {
+                error!("Manifest entry must have either device_id, or vid and pid: {:?}", self);
+                Err(Error::InvalidParam)
+            }
+        }
+    }
+
+    /// Apply the entry's settings to the installer for its device.  `base`
+    /// is the directory relative INF paths are resolved against.
+    fn configure(&self, installer: DriverInstaller, base: &Path) -> DriverInstaller {
+        let mut installer = installer.force_replace(self.force_replace);
+        if let Some(driver_type) = self.driver_type {
+            installer = installer.with_driver_type(driver_type);
+        }
+        if let Some(inf) = &self.inf {
+            installer = installer.with_inf_file(base.join(inf));
+        }
+        if let Some(vendor_name) = &self.vendor_name {
+            installer = installer.with_vendor_name(vendor_name);
+        }
+        if let Some(description) = &self.device_description {
+            installer = installer.with_device_description(description);
+        }
+        installer
+    }
+}
+
+/// Parse a manifest, as TOML if `path` has a `.toml` extension, otherwise as
+/// JSON.
+fn parse_manifest(path: &Path, text: &str) -> Result<Manifest, Error> {
+    let is_toml = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+    let result = if is_toml {
+        toml::from_str(text).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_str(text).map_err(|e| e.to_string())
+    };
+
+    result.map_err(|e| {
+        error!("Failed to parse manifest {}: {}", path.display(), e);
+        Error::InvalidParam
+    })
+}
+
+/// Install drivers for the devices listed in a TOML or JSON manifest file.
+///
+/// This allows a driver deployment to be version-controlled as data.  The
+/// manifest is read as TOML if the file has a `.toml` extension, and as JSON
+/// otherwise.  It contains a `devices` array, each entry of which has the
+/// following fields, all optional except as noted:
+///
+/// | Field                | Type    | Meaning |
+/// |----------------------|---------|---------|
+/// | `vid`, `pid`         | integer | USB Vendor and Product ID - required unless `device_id` is given |
+/// | `mi`                 | integer | Interface number, for an interface of a composite device |
+/// | `serial`             | string  | Serial number, as returned by [`Device::serial_number`] |
+/// | `device_id`          | string  | Device instance ID, instead of `vid`, `pid`, `mi` and `serial` |
+/// | `driver_type`        | string  | `"WinUsb"` (the default), `"LibUsb0"`, `"LibUsbK"`, `"Cdc"` or `"User"` |
+/// | `inf`                | string  | INF file to install, relative to the manifest's directory - see [`DriverInstaller::with_inf_file`] |
+/// | `vendor_name`        | string  | See [`DriverInstaller::with_vendor_name`] |
+/// | `device_description` | string  | See [`DriverInstaller::with_device_description`] |
+/// | `force_replace`      | boolean | See [`DriverInstaller::force_replace`] |
+///
+/// For example, in TOML:
+///
+/// ```toml
+/// [[devices]]
+/// vid = 0x1234
+/// pid = 0x5678
+/// serial = "ABC123"
+///
+/// [[devices]]
+/// vid = 0x1234
+/// pid = 0x9abc
+/// mi = 1
+/// driver_type = "Cdc"
+/// inf = "drivers/widget_cdc.inf"
+/// ```
+///
+/// As with [`install_targets_typed`](crate::install_targets_typed), devices
+/// are enumerated once and each entry is matched against that enumeration.
+/// Entries are processed in order, and one failing does not prevent the
+/// others being attempted.
+///
+/// Returns the target and result for each entry, in the same order.
+///
+/// # Errors
+/// * Returns `Error::Resource` if the manifest can't be read.
+/// * Returns `Error::InvalidParam` if the manifest is invalid, in which case
+///   nothing is installed.
+/// * Returns any error from enumerating the devices.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::Path;
+/// use wdi_rs::install_from_manifest;
+///
+/// for (target, result) in install_from_manifest(Path::new("drivers.toml"))? {
+///     match result {
+///         Ok(device) => println!("{}: installed driver for {}", target, device),
+///         Err(e) => println!("{}: failed: {}", target, e),
+///     }
+/// }
+/// # Ok::<(), wdi_rs::Error>(())
+/// ```
+pub fn install_from_manifest(path: &Path) -> Result<ManifestResults, Error> {
+    info!("Installing drivers from manifest {}", path.display());
+
+    let text = fs::read_to_string(path).map_err(|e| {
+        error!("Failed to read manifest {}: {}", path.display(), e);
+        Error::Resource
+    })?;
+    let manifest = parse_manifest(path, &text)?;
+
+    // Validate every entry before installing anything
+    let targets = manifest.devices.iter()
+        .map(ManifestEntry::target)
+        .collect::<Result<Vec<_>, _>>()?;
+    debug!("Manifest lists {} device(s)", targets.len());
+
+    let base = path.parent().unwrap_or(Path::new(""));
+    let entries = targets.into_iter().zip(&manifest.devices).collect();
+    install_targets_with(entries, |installer, entry| entry.configure(installer, base))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let toml = "[[devices]]\nvid = 0x1234\npid = 0x5678\nserial = \"ABC123\"\n\n\
+                    [[devices]]\ndevice_id = 'USB\\VID_1234&PID_9ABC\\1'\ndriver_type = \"Cdc\"\n";
+        let manifest = parse_manifest(Path::new("drivers.toml"), toml).unwrap();
+        assert_eq!(manifest.devices.len(), 2);
+        assert_eq!(
+            manifest.devices[0].target().unwrap(),
+            DeviceTarget::Usb { vid: 0x1234, pid: 0x5678, mi: None, serial: Some("ABC123".to_string()) },
+        );
+        assert_eq!(manifest.devices[1].target().unwrap(), DeviceTarget::DeviceId("USB\\VID_1234&PID_9ABC\\1".to_string()));
+        assert_eq!(manifest.devices[1].driver_type, Some(DriverType::Cdc));
+
+        let json = r#"{"devices": [{"vid": 4660, "pid": 22136, "mi": 1, "inf": "widget.inf"}]}"#;
+        let manifest = parse_manifest(Path::new("drivers.json"), json).unwrap();
+        assert_eq!(manifest.devices[0].inf, Some(PathBuf::from("widget.inf")));
+
+        // Incomplete targets and unknown fields are rejected
+        let manifest = parse_manifest(Path::new("drivers.json"), r#"{"devices": [{"vid": 4660}]}"#).unwrap();
+        assert!(manifest.devices[0].target().is_err());
+        assert!(parse_manifest(Path::new("drivers.json"), r#"{"devices": [{"vid": 1, "pid": 2, "drvier": "x"}]}"#).is_err());
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Polling for USB devices being added, removed or changed.
+
+use crate::{create_list, CreateListOptions, Device, DeviceKey, Error};
+use crate::ring_log::{debug, trace};
+use std::collections::HashMap;
+
+/// The devices which changed between two polls of a [`DeviceMonitor`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceDiff {
+    /// Devices which weren't present at the previous poll
+    pub added: Vec<Device>,
+    /// Devices which were present at the previous poll, but no longer are,
+    /// with their details as they were then
+    pub removed: Vec<Device>,
+    /// Devices which are still present, but whose details - such as their
+    /// driver - have changed, with their new details
+    pub changed: Vec<Device>,
+}
+
+impl DeviceDiff {
+    /// Returns `true` if no devices were added, removed or changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Tracks the connected USB devices across repeated enumerations, reporting
+/// what changed on each [`poll`](DeviceMonitor::poll).
+///
+/// This is a polling alternative to registering for device notifications,
+/// for environments where that isn't possible, such as services without a
+/// window.  Devices are matched between polls by their [`DeviceKey`], or by
+/// their `device_id` where two connected devices share a key.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::{thread, time::Duration};
+/// use wdi_rs::{CreateListOptions, DeviceMonitor};
+///
+/// let mut monitor = DeviceMonitor::new(CreateListOptions::all());
+/// loop {
+///     let diff = monitor.poll()?;
+///     for device in &diff.added {
+///         println!("Added: {}", device);
+///     }
+///     for device in &diff.removed {
+///         println!("Removed: {}", device);
+///     }
+///     for device in &diff.changed {
+///         println!("Changed: {} (driver {:?})", device, device.driver);
+///     }
+///     thread::sleep(Duration::from_secs(1));
+/// }
+/// # Ok::<(), wdi_rs::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct DeviceMonitor {
+    options: CreateListOptions,
+    snapshot: Vec<(DeviceKey, Device)>,
+}
+
+impl DeviceMonitor {
+    /// Creates a monitor enumerating devices with `options`.
+    ///
+    /// Nothing is enumerated until the first [`poll`](DeviceMonitor::poll),
+    /// which reports every connected device as added.
+    pub fn new(options: CreateListOptions) -> Self {
+        DeviceMonitor { options, snapshot: Vec::new() }
+    }
+
+    /// Enumerates the connected devices, and returns which were added,
+    /// removed or changed since the previous poll.
+    ///
+    /// # Errors
+    /// * Returns any error from enumerating the devices, other than
+    ///   `Error::NoDevice`, which is treated as no devices being connected.
+    ///   The previous snapshot is kept, so the next successful poll reports
+    ///   changes since the last successful one.
+    pub fn poll(&mut self) -> Result<DeviceDiff, Error> {
+        let devices = match create_list(self.options.clone()) {
+            Ok(devices) => devices.into_vec(),
+            Err(Error::NoDevice) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let current = key_devices(devices, Device::key);
+
+        let diff = diff(&self.snapshot, &current);
+        debug!("Device poll: {} added, {} removed, {} changed", diff.added.len(), diff.removed.len(), diff.changed.len());
+        self.snapshot = current;
+        Ok(diff)
+    }
+
+    /// The devices found by the most recent successful poll.
+    pub fn devices(&self) -> impl Iterator<Item = &Device> {
+        self.snapshot.iter().map(|(_, device)| device)
+    }
+}
+
+/// Key each device with `key`, falling back to its `device_id` for devices
+/// whose keys collide, so none are lost when the snapshots are compared.
+/// A device whose key starts or stops colliding is reported as removed and
+/// added again.
+fn key_devices(devices: Vec<Device>, key: impl Fn(&Device) -> DeviceKey) -> Vec<(DeviceKey, Device)> {
+    let keys: Vec<_> = devices.iter().map(key).collect();
+    let mut counts: HashMap<&DeviceKey, usize> = HashMap::new();
+    for key in &keys {
+        *counts.entry(key).or_default() += 1;
+    }
+
+    let keys: Vec<_> = keys.iter().zip(&devices)
+        .map(|(key, device)| if counts[key] > 1 {
+            trace!("Device shares its key with another - keying on device ID: {}", device);
+            device.device_id_key()
+        } else {
+            key.clone()
+        })
+        .collect();
+    keys.into_iter().zip(devices).collect()
+}
+
+/// Compare two snapshots of devices, keyed by [`DeviceKey`].
+fn diff(previous: &[(DeviceKey, Device)], current: &[(DeviceKey, Device)]) -> DeviceDiff {
+    let before: HashMap<_, _> = previous.iter().map(|(key, device)| (key, device)).collect();
+    let after: HashMap<_, _> = current.iter().map(|(key, device)| (key, device)).collect();
+
+    let mut diff = DeviceDiff::default();
+    for (key, device) in current {
+        match before.get(key) {
+            None => diff.added.push(device.clone()),
+            Some(old) if details_changed(old, device) => {
+                trace!("Device changed: {}", device);
+                diff.changed.push(device.clone());
+            }
+            Some(_) => {}
+        }
+    }
+    diff.removed = previous.iter()
+        .filter(|(key, _)| !after.contains_key(key))
+        .map(|(_, device)| device.clone())
+        .collect();
+    diff
+}
+
+/// Whether any of the details libwdi reports for a device differ.
+///
+/// This includes the identity fields `Device` compares, as a device keyed by
+/// its container ID keeps its key when re-enumerated with a new `device_id`.
+fn details_changed(a: &Device, b: &Device) -> bool {
+    a != b
+        || a.is_composite != b.is_composite
+        || a.desc != b.desc
+        || a.driver != b.driver
+        || a.hardware_id != b.hardware_id
+        || a.compatible_id != b.compatible_id
+        || a.upper_filter != b.upper_filter
+        || a.driver_version != b.driver_version
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wdi::test_device;
+
+    #[test]
+    fn test_diff() {
+        let device = |serial: &str, driver: Option<&str>| Device {
+            driver: driver.map(str::to_string),
+            device_id: Some(format!("USB\\VID_1234&PID_5678\\{}", serial)),
+            ..test_device(0x1234, 0x5678)
+        };
+        let keyed = |devices: Vec<Device>| key_devices(devices, Device::key);
+
+        let first = keyed(vec![device("A", None), device("B", None)]);
+        let diff1 = diff(&[], &first);
+        assert_eq!(diff1.added.len(), 2);
+        assert!(diff1.removed.is_empty() && diff1.changed.is_empty());
+        assert!(diff(&first, &first).is_empty());
+
+        let second = keyed(vec![device("A", Some("WinUSB")), device("C", None)]);
+        let diff2 = diff(&first, &second);
+        assert_eq!(diff2.added, [device("C", None)]);
+        assert_eq!(diff2.removed, [device("B", None)]);
+        assert_eq!(diff2.changed.len(), 1);
+        assert_eq!(diff2.changed[0].driver.as_deref(), Some("WinUSB"));
+    }
+
+    #[test]
+    fn test_diff_colliding_keys() {
+        let device = |serial: &str| Device {
+            device_id: Some(format!("USB\\VID_1234&PID_5678\\{}", serial)),
+            ..test_device(0x1234, 0x5678)
+        };
+        // Key every device on its VID and PID alone, so they all collide
+        let keyed = |devices: Vec<Device>| key_devices(devices, |d| test_device(d.vid, d.pid).key());
+
+        let second = keyed(vec![device("A"), device("B")]);
+        let diff1 = diff(&[], &second);
+        assert_eq!(diff1.added, [device("A"), device("B")]);
+
+        let third = keyed(vec![device("B"), device("C")]);
+        let diff2 = diff(&second, &third);
+        assert_eq!(diff2.added, [device("C")]);
+        assert_eq!(diff2.removed, [device("A")]);
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Durable JSON records of driver installations.
+
+use crate::{Device, DriverType, Error, InstallOutcome};
+use crate::ring_log::{debug, error};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tempfile::NamedTempFile;
+
+/// A record of a successful driver installation, as written by
+/// [`DriverInstaller::install_recording_to`](crate::DriverInstaller::install_recording_to).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstallRecord {
+    /// USB Vendor ID
+    pub vid: u16,
+    /// USB Product ID
+    pub pid: u16,
+    /// Interface number, for an interface of a composite device
+    pub mi: Option<u8>,
+    /// Device instance ID
+    pub device_id: Option<String>,
+    /// The driver type installed
+    pub driver_type: DriverType,
+    /// The published INF name Windows bound the device to, if known
+    pub inf: Option<String>,
+    /// When the driver was installed, in seconds since the Unix epoch
+    pub installed_at: u64,
+}
+
+impl InstallRecord {
+    pub(crate) fn new(outcome: &InstallOutcome, driver_type: DriverType) -> Self {
+        let device = &outcome.device;
+        let installed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        InstallRecord {
+            vid: device.vid,
+            pid: device.pid,
+            mi: device.is_composite.then_some(device.mi),
+            device_id: device.device_id.clone(),
+            driver_type,
+            inf: outcome.selected_inf.clone(),
+            installed_at,
+        }
+    }
+
+    /// Returns `true` if this record is for the given device, by device
+    /// instance ID, or VID, PID and interface if either has no ID.
+    pub fn matches(&self, device: &Device) -> bool {
+        match (&self.device_id, &device.device_id) {
+            (Some(id), Some(other)) => id.eq_ignore_ascii_case(other),
+            _ => {
+                self.vid == device.vid
+                    && self.pid == device.pid
+                    && self.mi == device.is_composite.then_some(device.mi)
+            }
+        }
+    }
+}
+
+/// Read the installation records from a JSON state file written by
+/// [`DriverInstaller::install_recording_to`](crate::DriverInstaller::install_recording_to).
+///
+/// Returns no records if the file doesn't exist yet.
+///
+/// # Errors
+/// * Returns `Error::Resource` if the file can't be read or isn't a valid
+///   state file.
+pub fn read_install_records(path: &Path) -> Result<Vec<InstallRecord>, Error> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            error!("Failed to read install records {}: {}", path.display(), e);
+            return Err(Error::Resource);
+        }
+    };
+
+    if data.iter().all(u8::is_ascii_whitespace) {
+        return Ok(Vec::new());
+    }
+
+    serde_json::from_slice(&data).map_err(|e| {
+        error!("Failed to parse install records {}: {}", path.display(), e);
+        Error::Resource
+    })
+}
+
+/// Append a record to the JSON state file at `path`, creating it if needed.
+///
+/// A lock file alongside the state file serializes writers across processes.
+/// It's deliberately never removed, as another writer may be waiting on it,
+/// and deleting it would let a later writer lock a new file concurrently.
+/// The updated file is written to a temporary file and renamed into
+/// place, so readers never see a partially written file.
+pub(crate) fn append_install_record(path: &Path, record: InstallRecord) -> Result<(), Error> {
+    debug!("Recording installation to {}", path.display());
+
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    let lock_path = PathBuf::from(lock_path);
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| {
+            error!("Failed to open lock file {}: {}", lock_path.display(), e);
+            Error::Resource
+        })?;
+    lock.lock().map_err(|e| {
+        error!("Failed to lock {}: {}", lock_path.display(), e);
+        Error::Resource
+    })?;
+
+    let mut records = read_install_records(path)?;
+    records.push(record);
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let write = || -> io::Result<()> {
+        let mut file = NamedTempFile::new_in(dir)?;
+        serde_json::to_writer_pretty(&mut file, &records)?;
+        file.write_all(b"\n")?;
+        file.as_file().sync_all()?;
+        file.persist(path)?;
+        Ok(())
+    };
+    write().map_err(|e| {
+        error!("Failed to write install records {}: {}", path.display(), e);
+        Error::Resource
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_and_read_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("drivers.json");
+        assert!(read_install_records(&path).unwrap().is_empty());
+
+        let record = InstallRecord {
+            vid: 0x1234,
+            pid: 0x5678,
+            mi: None,
+            device_id: Some("USB\\VID_1234&PID_5678\\ABC123".to_string()),
+            driver_type: DriverType::WinUsb,
+            inf: Some("oem12.inf".to_string()),
+            installed_at: 0,
+        };
+        append_install_record(&path, record.clone()).unwrap();
+        append_install_record(&path, InstallRecord { pid: 0x9abc, ..record.clone() }).unwrap();
+
+        let records = read_install_records(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0], record);
+        assert_eq!(records[1].pid, 0x9abc);
+    }
+}
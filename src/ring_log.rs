@@ -0,0 +1,114 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Optional in-memory ring buffer capturing the crate's recent log messages,
+//! for attaching to crash reports.
+//!
+//! The crate's modules log through the macros defined here rather than
+//! directly through the `log` crate, so messages are captured at every level
+//! regardless of how (or whether) the application has configured logging.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+struct RingLog {
+    capacity: usize,
+    entries: VecDeque<String>,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static RING_LOG: Mutex<Option<RingLog>> = Mutex::new(None);
+
+/// Enables capture of the last `capacity` log messages into an in-memory ring
+/// buffer, retrievable with [`dump_ring_log`].
+///
+/// Messages are captured at all levels, so an unattended installer can attach
+/// the lead-up to a failure to a crash report without verbose logging having
+/// been configured.  Calling this again resizes the buffer, keeping the most
+/// recent messages.  A `capacity` of 0 disables capture and discards the
+/// buffer.
+pub fn enable_ring_log(capacity: usize) {
+    let mut ring = RING_LOG.lock().unwrap_or_else(|e| e.into_inner());
+
+    if capacity == 0 {
+        ENABLED.store(false, Ordering::Relaxed);
+        *ring = None;
+        return;
+    }
+
+    let ring = ring.get_or_insert_with(|| RingLog {
+        capacity,
+        entries: VecDeque::with_capacity(capacity),
+    });
+    ring.capacity = capacity;
+    while ring.entries.len() > capacity {
+        ring.entries.pop_front();
+    }
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Returns the messages currently held in the ring buffer, oldest first.
+///
+/// Returns an empty vector if [`enable_ring_log`] has not been called.
+pub fn dump_ring_log() -> Vec<String> {
+    let ring = RING_LOG.lock().unwrap_or_else(|e| e.into_inner());
+    ring.as_ref()
+        .map(|ring| ring.entries.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+pub(crate) fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Records a message in the ring buffer, evicting the oldest if full.
+pub(crate) fn record(level: log::Level, message: &str) {
+    let mut ring = RING_LOG.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(ring) = ring.as_mut() {
+        if ring.entries.len() == ring.capacity {
+            ring.entries.pop_front();
+        }
+        ring.entries.push_back(format!("{:<5} {}", level, message));
+    }
+}
+
+/// Logs via the `log` crate, also recording the message in the ring buffer
+/// if enabled.  The message is only formatted if one of them wants it.
+macro_rules! ring_log {
+    ($level:expr, $($arg:tt)+) => {{
+        let level = $level;
+        let ring = $crate::ring_log::is_enabled();
+        if ring || ::log::log_enabled!(level) {
+            let message = format!($($arg)+);
+            if ring {
+                $crate::ring_log::record(level, &message);
+            }
+            ::log::log!(level, "{}", message);
+        }
+    }};
+}
+
+macro_rules! error {
+    ($($arg:tt)+) => { $crate::ring_log::ring_log!(::log::Level::Error, $($arg)+) };
+}
+
+// Named differently to avoid clashing with the built-in `warn` attribute
+macro_rules! ring_warn {
+    ($($arg:tt)+) => { $crate::ring_log::ring_log!(::log::Level::Warn, $($arg)+) };
+}
+
+macro_rules! info {
+    ($($arg:tt)+) => { $crate::ring_log::ring_log!(::log::Level::Info, $($arg)+) };
+}
+
+macro_rules! debug {
+    ($($arg:tt)+) => { $crate::ring_log::ring_log!(::log::Level::Debug, $($arg)+) };
+}
+
+macro_rules! trace {
+    ($($arg:tt)+) => { $crate::ring_log::ring_log!(::log::Level::Trace, $($arg)+) };
+}
+
+pub(crate) use {ring_log, error, ring_warn as warn, info, debug, trace};
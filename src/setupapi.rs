@@ -0,0 +1,487 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Safe wrappers around the SetupAPI calls used for device property queries
+//! and driver store operations that libwdi doesn't expose.
+
+use crate::ffi::{DevPropKey, HDevInfo, HKey, SpDevinfoData, Guid, DEVPROP_TYPE_STRING, DEVPROP_TYPE_UINT32, SUOI_FORCEDELETE};
+use crate::ffi::{DICS_FLAG_GLOBAL, DIREG_DEV, KEY_READ, REG_SZ, DIGCF_PRESENT, DIGCF_DEVICEINTERFACE};
+use crate::ffi::{SpDeviceInterfaceData, SystemTime, DEVPROP_TYPE_FILETIME, DEVPROP_TYPE_GUID, FileTimeToSystemTime};
+use crate::ffi::{ERROR_FILE_NOT_FOUND, ERROR_ACCESS_DENIED, ERROR_INF_IN_USE_BY_DEVICES};
+use crate::ffi::{SetupDiCreateDeviceInfoList, SetupDiOpenDeviceInfoW, SetupDiGetDevicePropertyW, SetupDiDestroyDeviceInfoList};
+use crate::ffi::{SetupUninstallOEMInfW, SetupDiOpenDevRegKey, RegQueryValueExW, RegCloseKey};
+use crate::ffi::SetupGetInfDriverStoreLocationW;
+use crate::ffi::{SetupDiGetClassDevsW, SetupDiEnumDeviceInterfaces, SetupDiGetDeviceInterfaceDetailW};
+use crate::ffi::SP_DEVICE_INTERFACE_DETAIL_DATA_SIZE;
+use crate::{Device, DeviceInterfaceGuid, Error};
+use crate::ring_log::{debug, error, info};
+use std::path::PathBuf;
+use std::ptr;
+
+/// Converts a Rust string to a NUL-terminated UTF-16 string.
+pub(crate) fn to_wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// Converts a (possibly NUL-terminated) UTF-16 buffer to a Rust string.
+pub(crate) fn from_wide(buf: &[u16]) -> String {
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    String::from_utf16_lossy(&buf[..len])
+}
+
+/// A single device, opened via SetupAPI by its device instance ID.
+///
+/// The underlying device information set is destroyed on drop.
+pub(crate) struct DeviceInfo {
+    set: HDevInfo,
+    data: SpDevinfoData,
+}
+
+impl DeviceInfo {
+    /// Opens the device with the given instance ID (as reported in
+    /// [`Device::device_id`](crate::Device::device_id)).
+    ///
+    /// Returns `None` if the device is unknown to Windows.
+    pub(crate) fn open(instance_id: &str) -> Option<Self> {
+        let set = unsafe { SetupDiCreateDeviceInfoList(ptr::null(), ptr::null_mut()) };
+        if set.is_null() || set as isize == -1 {
+            return None;
+        }
+
+        let mut info = DeviceInfo {
+            set,
+            data: SpDevinfoData {
+                cb_size: std::mem::size_of::<SpDevinfoData>() as u32,
+                class_guid: Guid { data1: 0, data2: 0, data3: 0, data4: [0; 8] },
+                dev_inst: 0,
+                reserved: 0,
+            },
+        };
+
+        let id = to_wide(instance_id);
+        let ok = unsafe {
+            SetupDiOpenDeviceInfoW(info.set, id.as_ptr(), ptr::null_mut(), 0, &mut info.data)
+        };
+        if ok == 0 {
+            return None;
+        }
+
+        Some(info)
+    }
+
+    /// Reads a string-typed device property, returning `None` if it is not
+    /// set or has a different type.
+    pub(crate) fn property_string(&self, key: &DevPropKey) -> Option<String> {
+        let mut prop_type = 0u32;
+        let mut required = 0u32;
+
+        // First call to size the buffer
+        unsafe {
+            SetupDiGetDevicePropertyW(
+                self.set,
+                &self.data,
+                key,
+                &mut prop_type,
+                ptr::null_mut(),
+                0,
+                &mut required,
+                0,
+            );
+        }
+        if required == 0 {
+            return None;
+        }
+
+        let mut buf = vec![0u16; (required as usize).div_ceil(2)];
+        let ok = unsafe {
+            SetupDiGetDevicePropertyW(
+                self.set,
+                &self.data,
+                key,
+                &mut prop_type,
+                buf.as_mut_ptr() as *mut u8,
+                (buf.len() * 2) as u32,
+                &mut required,
+                0,
+            )
+        };
+        if ok == 0 || prop_type != DEVPROP_TYPE_STRING {
+            return None;
+        }
+
+        Some(from_wide(&buf))
+    }
+
+    /// Reads a 32-bit unsigned device property, returning `None` if it is not
+    /// set or has a different type.
+    pub(crate) fn property_u32(&self, key: &DevPropKey) -> Option<u32> {
+        let mut prop_type = 0u32;
+        let mut value = 0u32;
+        let ok = unsafe {
+            SetupDiGetDevicePropertyW(
+                self.set,
+                &self.data,
+                key,
+                &mut prop_type,
+                &mut value as *mut u32 as *mut u8,
+                std::mem::size_of::<u32>() as u32,
+                ptr::null_mut(),
+                0,
+            )
+        };
+        if ok == 0 || prop_type != DEVPROP_TYPE_UINT32 {
+            return None;
+        }
+
+        Some(value)
+    }
+
+    /// Reads a GUID device property, returning `None` if it is not set or has
+    /// a different type.
+    pub(crate) fn property_guid(&self, key: &DevPropKey) -> Option<Guid> {
+        let mut prop_type = 0u32;
+        let mut value = Guid { data1: 0, data2: 0, data3: 0, data4: [0; 8] };
+        let ok = unsafe {
+            SetupDiGetDevicePropertyW(
+                self.set,
+                &self.data,
+                key,
+                &mut prop_type,
+                &mut value as *mut Guid as *mut u8,
+                std::mem::size_of::<Guid>() as u32,
+                ptr::null_mut(),
+                0,
+            )
+        };
+        if ok == 0 || prop_type != DEVPROP_TYPE_GUID {
+            return None;
+        }
+
+        Some(value)
+    }
+
+    /// Reads a `FILETIME` device property as a `(year, month, day)` date,
+    /// returning `None` if it is not set or has a different type.
+    pub(crate) fn property_date(&self, key: &DevPropKey) -> Option<(u16, u16, u16)> {
+        let mut prop_type = 0u32;
+        let mut file_time = 0u64;
+        let ok = unsafe {
+            SetupDiGetDevicePropertyW(
+                self.set,
+                &self.data,
+                key,
+                &mut prop_type,
+                &mut file_time as *mut u64 as *mut u8,
+                std::mem::size_of::<u64>() as u32,
+                ptr::null_mut(),
+                0,
+            )
+        };
+        if ok == 0 || prop_type != DEVPROP_TYPE_FILETIME {
+            return None;
+        }
+
+        let mut time = SystemTime::default();
+        if unsafe { FileTimeToSystemTime(&file_time, &mut time) } == 0 {
+            return None;
+        }
+
+        Some((time.year, time.month, time.day))
+    }
+
+    /// Opens the device's hardware (`Device Parameters`) registry key.  The
+    /// caller must close it.
+    fn open_registry_key(&self) -> Option<HKey> {
+        let key = unsafe {
+            SetupDiOpenDevRegKey(self.set, &self.data, DICS_FLAG_GLOBAL, 0, DIREG_DEV, KEY_READ)
+        };
+        if key.is_null() || key as isize == -1 {
+            return None;
+        }
+        Some(key)
+    }
+
+    /// Checks whether a value of any type exists in the device's hardware
+    /// registry key.
+    pub(crate) fn registry_value_exists(&self, value_name: &str) -> bool {
+        let Some(key) = self.open_registry_key() else {
+            return false;
+        };
+
+        let name = to_wide(value_name);
+        let result = unsafe {
+            RegQueryValueExW(key, name.as_ptr(), ptr::null_mut(), ptr::null_mut(), ptr::null_mut(), ptr::null_mut())
+        };
+        unsafe {
+            RegCloseKey(key);
+        }
+
+        result == 0
+    }
+
+    /// Reads a string value from the device's hardware (`Device Parameters`)
+    /// registry key, returning `None` if it is not set or not a string.
+    pub(crate) fn registry_string(&self, value_name: &str) -> Option<String> {
+        let key = self.open_registry_key()?;
+
+        let name = to_wide(value_name);
+        let mut value_type = 0u32;
+        let mut len = 0u32;
+
+        // First call to size the buffer
+        let result = unsafe {
+            RegQueryValueExW(
+                key,
+                name.as_ptr(),
+                ptr::null_mut(),
+                &mut value_type,
+                ptr::null_mut(),
+                &mut len,
+            )
+        };
+        let mut buf = vec![0u16; (len as usize).div_ceil(2)];
+        let result = if result == 0 && value_type == REG_SZ && len > 0 {
+            unsafe {
+                RegQueryValueExW(
+                    key,
+                    name.as_ptr(),
+                    ptr::null_mut(),
+                    &mut value_type,
+                    buf.as_mut_ptr() as *mut u8,
+                    &mut len,
+                )
+            }
+        } else {
+            result
+        };
+        unsafe {
+            RegCloseKey(key);
+        }
+
+        if result != 0 || value_type != REG_SZ {
+            return None;
+        }
+
+        buf.truncate((len as usize) / 2);
+        Some(from_wide(&buf))
+    }
+}
+
+impl Drop for DeviceInfo {
+    fn drop(&mut self) {
+        unsafe {
+            SetupDiDestroyDeviceInfoList(self.set);
+        }
+    }
+}
+
+/// Checks whether a present device exposes a device interface with the given
+/// GUID.
+///
+/// If `instance_id` is provided only that device is checked, otherwise any
+/// device will do.
+pub(crate) fn interface_registered(instance_id: Option<&str>, guid: &Guid) -> bool {
+    let id = instance_id.map(to_wide);
+    let set = unsafe {
+        SetupDiGetClassDevsW(
+            guid,
+            id.as_ref().map_or(ptr::null(), |id| id.as_ptr()),
+            ptr::null_mut(),
+            DIGCF_PRESENT | DIGCF_DEVICEINTERFACE,
+        )
+    };
+    if set.is_null() || set as isize == -1 {
+        return false;
+    }
+
+    let mut data = SpDeviceInterfaceData {
+        cb_size: std::mem::size_of::<SpDeviceInterfaceData>() as u32,
+        interface_class_guid: *guid,
+        flags: 0,
+        reserved: 0,
+    };
+    let found = unsafe { SetupDiEnumDeviceInterfaces(set, ptr::null(), guid, 0, &mut data) } != 0;
+
+    unsafe {
+        SetupDiDestroyDeviceInfoList(set);
+    }
+    found
+}
+
+/// Gets the device interface path for an installed device, as passed to
+/// `CreateFile` to open the device - for example
+/// `\\?\usb#vid_1234&pid_5678#abc123#{6e45736a-2b1b-4078-b772-b3af2b6fde1c}`.
+///
+/// This is typically the next step after installing WinUSB, using the
+/// device interface GUID the driver was installed with.
+///
+/// # Arguments
+/// * `device` - The device, which must have an instance ID.
+/// * `interface_guid` - The device interface GUID to look up.
+///
+/// # Errors
+/// * `Error::InvalidParam` if the device has no instance ID.
+/// * `Error::NotFound` if the device is not present, or doesn't expose an
+///   interface with the given GUID.
+/// * `Error::Resource` if the path can't be retrieved.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use wdi_rs::{get_device_interface_path, DriverInstaller, DeviceInterfaceGuid};
+///
+/// let guid: DeviceInterfaceGuid = "{6e45736a-2b1b-4078-b772-b3af2b6fde1c}".parse()?;
+/// let device = DriverInstaller::for_device(0x1234, 0x5678)
+///     .ensure_interface_registered(guid, Duration::from_secs(10))
+///     .install()?;
+///
+/// let path = get_device_interface_path(&device, &guid)?;
+/// println!("Open the device at {}", path);
+/// # Ok::<(), wdi_rs::Error>(())
+/// ```
+pub fn get_device_interface_path(device: &Device, interface_guid: &DeviceInterfaceGuid) -> Result<String, Error> {
+    let Some(instance_id) = device.device_id.as_deref() else {
+        error!("Device {} has no instance ID", device);
+        return Err(Error::InvalidParam);
+    };
+
+    debug!("Getting device interface {} path for {}", interface_guid, instance_id);
+    let id = to_wide(instance_id);
+    let guid = interface_guid.to_ffi();
+    let set = unsafe {
+        SetupDiGetClassDevsW(&guid, id.as_ptr(), ptr::null_mut(), DIGCF_PRESENT | DIGCF_DEVICEINTERFACE)
+    };
+    if set.is_null() || set as isize == -1 {
+        debug!("Device {} not present", instance_id);
+        return Err(Error::NotFound);
+    }
+
+    let result = interface_path(set, &guid);
+    unsafe {
+        SetupDiDestroyDeviceInfoList(set);
+    }
+
+    match &result {
+        Ok(path) => info!("Device interface path for {}: {}", instance_id, path),
+        Err(Error::NotFound) => debug!("Device {} has no interface {}", instance_id, interface_guid),
+        Err(_) => (),
+    }
+    result
+}
+
+/// Gets the path of the first interface with the given GUID in a device
+/// information set.
+fn interface_path(set: HDevInfo, guid: &Guid) -> Result<String, Error> {
+    let mut data = SpDeviceInterfaceData {
+        cb_size: std::mem::size_of::<SpDeviceInterfaceData>() as u32,
+        interface_class_guid: *guid,
+        flags: 0,
+        reserved: 0,
+    };
+    if unsafe { SetupDiEnumDeviceInterfaces(set, ptr::null(), guid, 0, &mut data) } == 0 {
+        return Err(Error::NotFound);
+    }
+
+    // First call gets the required size
+    let mut size: u32 = 0;
+    unsafe {
+        SetupDiGetDeviceInterfaceDetailW(set, &data, ptr::null_mut(), 0, &mut size, ptr::null_mut());
+    }
+    if (size as usize) <= SP_DEVICE_INTERFACE_DETAIL_DATA_SIZE as usize {
+        error!("Failed to get device interface detail size: {}", std::io::Error::last_os_error());
+        return Err(Error::Resource);
+    }
+
+    // Use a u32 buffer, so the cbSize field is suitably aligned
+    let mut buf = vec![0u32; (size as usize).div_ceil(4)];
+    let detail = buf.as_mut_ptr() as *mut u8;
+    unsafe {
+        *(detail as *mut u32) = SP_DEVICE_INTERFACE_DETAIL_DATA_SIZE;
+    }
+    if unsafe { SetupDiGetDeviceInterfaceDetailW(set, &data, detail, size, ptr::null_mut(), ptr::null_mut()) } == 0 {
+        error!("Failed to get device interface detail: {}", std::io::Error::last_os_error());
+        return Err(Error::Resource);
+    }
+
+    // DevicePath follows the cbSize field, and is NUL terminated
+    let path_units = (size as usize - std::mem::size_of::<u32>()) / 2;
+    let path = unsafe {
+        std::slice::from_raw_parts(detail.add(std::mem::size_of::<u32>()) as *const u16, path_units)
+    };
+    Ok(from_wide(path))
+}
+
+/// Finds the driver store copy of a published INF (e.g. `oem12.inf`), whose
+/// directory holds the rest of the driver package's files.
+///
+/// Returns `None` if the INF isn't in the driver store.
+pub(crate) fn driver_store_inf(inf_name: &str) -> Option<PathBuf> {
+    let name = to_wide(inf_name);
+    let mut buf = vec![0u16; 260];
+    loop {
+        let mut required: u32 = 0;
+        let ok = unsafe {
+            SetupGetInfDriverStoreLocationW(
+                name.as_ptr(),
+                ptr::null(),
+                ptr::null(),
+                buf.as_mut_ptr(),
+                buf.len() as u32,
+                &mut required,
+            )
+        };
+        if ok != 0 {
+            return Some(PathBuf::from(from_wide(&buf)));
+        }
+        if required as usize <= buf.len() {
+            debug!("No driver store location for {}: {}", inf_name, std::io::Error::last_os_error());
+            return None;
+        }
+        buf.resize(required as usize, 0);
+    }
+}
+
+/// Removes a published driver package from the driver store by its OEM INF
+/// name (e.g. `oem42.inf`).
+///
+/// This is the counterpart to installing a driver, allowing uninstallers to
+/// remove exactly the packages they published.  If `force` is `true` the
+/// package is removed even if devices are currently using it.
+///
+/// # Arguments
+/// * `oem_name` - The published INF name, without any path.
+/// * `force` - Whether to remove the package even if it is in use.
+///
+/// # Errors
+/// * `Error::InvalidParam` if `oem_name` is empty or contains a path.
+/// * `Error::NotFound` if no such package is published.
+/// * `Error::Busy` if the package is in use and `force` is `false`.
+/// * `Error::NeedsAdmin` if the caller lacks the rights to remove it.
+/// * `Error::Io` for any other failure.
+pub fn uninstall_oem_inf(oem_name: &str, force: bool) -> Result<(), Error> {
+    if oem_name.is_empty() || oem_name.contains(['\\', '/']) {
+        error!("Invalid OEM INF name: {}", oem_name);
+        return Err(Error::InvalidParam);
+    }
+
+    debug!("Uninstalling OEM INF {} (force={})", oem_name, force);
+    let name = to_wide(oem_name);
+    let flags = if force { SUOI_FORCEDELETE } else { 0 };
+
+    let ok = unsafe { SetupUninstallOEMInfW(name.as_ptr(), flags, ptr::null_mut()) };
+    if ok == 0 {
+        let err = std::io::Error::last_os_error();
+        error!("Failed to uninstall OEM INF {}: {}", oem_name, err);
+        return Err(match err.raw_os_error() {
+            Some(ERROR_FILE_NOT_FOUND) => Error::NotFound,
+            Some(ERROR_ACCESS_DENIED) => Error::NeedsAdmin,
+            Some(ERROR_INF_IN_USE_BY_DEVICES) => Error::Busy,
+            _ => Error::Io,
+        });
+    }
+
+    info!("Uninstalled OEM INF {}", oem_name);
+    Ok(())
+}
@@ -0,0 +1,286 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Queries of system-wide Windows state relevant to driver installation.
+
+use crate::ffi::{HKey, HKEY_LOCAL_MACHINE, KEY_READ, REG_BINARY};
+use crate::ffi::{RegOpenKeyExW, RegQueryValueExW, RegCloseKey};
+use crate::ffi::{LocaleNameToLCID, GetThreadUILanguage, SetThreadUILanguage};
+use crate::ffi::{ScHandle, ServiceStatus, SC_MANAGER_CONNECT, SERVICE_QUERY_STATUS, SERVICE_START, SERVICE_STOP};
+use crate::ffi::{SERVICE_CONTROL_STOP, SERVICE_STOPPED, WAIT_OBJECT_0, ERROR_ACCESS_DENIED};
+use crate::ffi::{ERROR_SERVICE_ALREADY_RUNNING, ERROR_SERVICE_NOT_ACTIVE};
+use crate::ffi::{OpenSCManagerW, OpenServiceW, ControlService, QueryServiceStatus, StartServiceW, CloseServiceHandle};
+use crate::ffi::CMP_WaitNoPendingInstallEvents;
+use crate::Error;
+use crate::ring_log::{debug, error, info, warn};
+use crate::setupapi::to_wide;
+use std::ptr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Registry keys whose existence indicates a pending reboot.
+const REBOOT_PENDING_KEYS: &[&str] = &[
+    r"SOFTWARE\Microsoft\Windows\CurrentVersion\Component Based Servicing\RebootPending",
+    r"SOFTWARE\Microsoft\Windows\CurrentVersion\WindowsUpdate\Auto Update\RebootRequired",
+];
+
+/// Registry key and value whose existence indicates files are waiting to be
+/// replaced on reboot.
+const PENDING_FILE_RENAME: (&str, &str) = (
+    r"SYSTEM\CurrentControlSet\Control\Session Manager",
+    "PendingFileRenameOperations",
+);
+
+/// Checks whether Windows has a reboot pending from a prior operation.
+///
+/// Installing a driver while a reboot is pending can fail or leave the system
+/// in an inconsistent state, so deployment tooling may wish to reboot first.
+/// This checks the standard indicators - the `RebootPending` (Component Based
+/// Servicing) and `RebootRequired` (Windows Update) keys, and the
+/// `PendingFileRenameOperations` value.
+pub fn is_reboot_pending() -> bool {
+    for key in REBOOT_PENDING_KEYS {
+        if let Some(hkey) = open_hklm(key) {
+            close(hkey);
+            debug!("Reboot pending: {} exists", key);
+            return true;
+        }
+    }
+
+    let (key, value) = PENDING_FILE_RENAME;
+    if let Some(hkey) = open_hklm(key) {
+        let name = to_wide(value);
+        let mut len = 0u32;
+        let result = unsafe {
+            RegQueryValueExW(hkey, name.as_ptr(), ptr::null_mut(), ptr::null_mut(), ptr::null_mut(), &mut len)
+        };
+        close(hkey);
+        if result == 0 && len > 0 {
+            debug!("Reboot pending: {} has entries", value);
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Checks whether Windows has a device installation in progress.
+///
+/// This is the condition libwdi reports as `Error::PendingInstallation`.
+pub fn is_installation_pending() -> bool {
+    unsafe { CMP_WaitNoPendingInstallEvents(0) != WAIT_OBJECT_0 }
+}
+
+/// The service which performs device installations.
+const DEVICE_INSTALL_SERVICE: &str = "DeviceInstall";
+
+/// How long to wait for the device installation service to stop.
+const SERVICE_STOP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait, after restarting the device installation service, for
+/// pending installations to clear.
+const PENDING_CLEAR_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Attempts to clear a stuck pending device installation, so that a retry
+/// can succeed.
+///
+/// If an earlier installation was interrupted, Windows can be left believing
+/// one is still in progress, causing every subsequent installation to fail
+/// with `Error::PendingInstallation` until reboot.
+///
+/// If an installation is pending, this restarts the Device Install Service
+/// (`DeviceInstall`), which owns Windows' in-progress installation state, and
+/// then waits up to 10 seconds for the pending state to clear.  It does
+/// nothing if no installation is pending.
+///
+/// # Risks
+///
+/// This cannot distinguish a stuck installation from a genuine one which is
+/// simply slow - for example, Windows installing a driver for a device that
+/// was just plugged in.  Restarting the service aborts any such installation,
+/// which may leave that device without a working driver until it is
+/// reconnected.  Only call this after a genuine installation has had ample
+/// time to complete.  Requires administrator privileges.
+///
+/// # Errors
+/// * `Error::NeedsAdmin` if the service can't be controlled due to
+///   insufficient privileges.
+/// * `Error::Timeout` if the service doesn't stop in a reasonable time.
+/// * `Error::PendingInstallation` if an installation is still pending after
+///   restarting the service.
+/// * `Error::Io` if the service can't be controlled for another reason.
+pub fn clear_pending_installation() -> Result<(), Error> {
+    if !is_installation_pending() {
+        debug!("No device installation pending - nothing to clear");
+        return Ok(());
+    }
+
+    warn!("Device installation pending - restarting {} service", DEVICE_INSTALL_SERVICE);
+    restart_service(DEVICE_INSTALL_SERVICE)?;
+
+    if unsafe { CMP_WaitNoPendingInstallEvents(PENDING_CLEAR_TIMEOUT.as_millis() as u32) } != WAIT_OBJECT_0 {
+        error!("Device installation still pending after restarting {}", DEVICE_INSTALL_SERVICE);
+        return Err(Error::PendingInstallation);
+    }
+
+    info!("Cleared pending device installation");
+    Ok(())
+}
+
+/// Maps the last Windows error from a service control call to an `Error`.
+fn service_error(action: &str) -> Error {
+    let err = std::io::Error::last_os_error();
+    error!("Failed to {}: {}", action, err);
+    match err.raw_os_error() {
+        Some(ERROR_ACCESS_DENIED) => Error::NeedsAdmin,
+        _ => Error::Io,
+    }
+}
+
+/// Closes a service control handle when dropped.
+struct ServiceHandle(ScHandle);
+
+impl Drop for ServiceHandle {
+    fn drop(&mut self) {
+        unsafe {
+            CloseServiceHandle(self.0);
+        }
+    }
+}
+
+/// Stops a service, waiting for it to stop, then starts it again.
+fn restart_service(name: &str) -> Result<(), Error> {
+    let scm = unsafe { OpenSCManagerW(ptr::null(), ptr::null(), SC_MANAGER_CONNECT) };
+    if scm.is_null() {
+        return Err(service_error("open the service control manager"));
+    }
+    let scm = ServiceHandle(scm);
+
+    let wide_name = to_wide(name);
+    let service = unsafe {
+        OpenServiceW(scm.0, wide_name.as_ptr(), SERVICE_STOP | SERVICE_START | SERVICE_QUERY_STATUS)
+    };
+    if service.is_null() {
+        return Err(service_error(&format!("open the {} service", name)));
+    }
+    let service = ServiceHandle(service);
+
+    let mut status = ServiceStatus::default();
+    if unsafe { ControlService(service.0, SERVICE_CONTROL_STOP, &mut status) } == 0
+        && std::io::Error::last_os_error().raw_os_error() != Some(ERROR_SERVICE_NOT_ACTIVE)
+    {
+        return Err(service_error(&format!("stop the {} service", name)));
+    }
+
+    let start = Instant::now();
+    loop {
+        if unsafe { QueryServiceStatus(service.0, &mut status) } == 0 {
+            return Err(service_error(&format!("query the {} service", name)));
+        }
+        if status.current_state == SERVICE_STOPPED {
+            break;
+        }
+        if start.elapsed() >= SERVICE_STOP_TIMEOUT {
+            error!("Timed out waiting for the {} service to stop", name);
+            return Err(Error::Timeout);
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    debug!("Stopped the {} service", name);
+
+    // Windows restarts this service on demand, so one already running again
+    // is fine
+    if unsafe { StartServiceW(service.0, 0, ptr::null()) } == 0
+        && std::io::Error::last_os_error().raw_os_error() != Some(ERROR_SERVICE_ALREADY_RUNNING)
+    {
+        return Err(service_error(&format!("start the {} service", name)));
+    }
+
+    debug!("Started the {} service", name);
+    Ok(())
+}
+
+/// Registry key under which Windows caches per-device USB flags, in subkeys
+/// named by VID, PID and revision.
+const USB_FLAGS_KEY: &str = r"SYSTEM\CurrentControlSet\Control\usbflags";
+
+/// Reads whether Windows found a Microsoft OS descriptor on the device with
+/// the given VID, PID and revision (`bcdDevice`).
+///
+/// Windows queries the descriptor the first time it enumerates a device, and
+/// caches the result in the `osvc` value of the device's `usbflags` subkey -
+/// the first byte is 1 if the device has the descriptor.  Returns `None` if
+/// there is no cached result.
+pub(crate) fn os_descriptor_cached(vid: u16, pid: u16, rev: u16) -> Option<bool> {
+    let key = format!(r"{}\{:04X}{:04X}{:04X}", USB_FLAGS_KEY, vid, pid, rev);
+    let hkey = open_hklm(&key)?;
+
+    let name = to_wide("osvc");
+    let mut value_type = 0u32;
+    let mut data = [0u8; 2];
+    let mut len = data.len() as u32;
+    let result = unsafe {
+        RegQueryValueExW(hkey, name.as_ptr(), ptr::null_mut(), &mut value_type, data.as_mut_ptr(), &mut len)
+    };
+    close(hkey);
+    if result != 0 || value_type != REG_BINARY || len == 0 {
+        return None;
+    }
+
+    debug!("Cached OS descriptor flag for {}: {:02x?}", key, &data[..len as usize]);
+    Some(data[0] == 1)
+}
+
+fn open_hklm(sub_key: &str) -> Option<HKey> {
+    let sub_key = to_wide(sub_key);
+    let mut hkey: HKey = ptr::null_mut();
+    let result = unsafe { RegOpenKeyExW(HKEY_LOCAL_MACHINE, sub_key.as_ptr(), 0, KEY_READ, &mut hkey) };
+    (result == 0).then_some(hkey)
+}
+
+fn close(hkey: HKey) {
+    unsafe {
+        RegCloseKey(hkey);
+    }
+}
+
+/// Restores the thread's previous UI language when dropped.
+pub(crate) struct ThreadUiLanguage {
+    previous: u16,
+}
+
+impl ThreadUiLanguage {
+    /// Sets the calling thread's UI language to the given locale name (e.g.
+    /// `de-DE`), so any dialogs Windows shows on this thread use it.
+    ///
+    /// Returns `Error::InvalidParam` if the locale name is not recognised, or
+    /// `Error::NotSupported` if the language can't be set.
+    pub(crate) fn set(locale_name: &str) -> Result<Self, Error> {
+        let name = to_wide(locale_name);
+        let lcid = unsafe { LocaleNameToLCID(name.as_ptr(), 0) };
+        if lcid == 0 {
+            error!("Unrecognised UI language: {}", locale_name);
+            return Err(Error::InvalidParam);
+        }
+
+        // LANGIDFROMLCID
+        let lang_id = (lcid & 0xFFFF) as u16;
+        let previous = unsafe { GetThreadUILanguage() };
+        if unsafe { SetThreadUILanguage(lang_id) } != lang_id {
+            error!("Failed to set thread UI language to {} ({:#06x})", locale_name, lang_id);
+            return Err(Error::NotSupported);
+        }
+
+        debug!("Set thread UI language to {} ({:#06x})", locale_name, lang_id);
+        Ok(ThreadUiLanguage { previous })
+    }
+}
+
+impl Drop for ThreadUiLanguage {
+    fn drop(&mut self) {
+        if unsafe { SetThreadUILanguage(self.previous) } != self.previous {
+            warn!("Failed to restore thread UI language to {:#06x}", self.previous);
+        }
+    }
+}
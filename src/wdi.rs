@@ -4,12 +4,29 @@
 
 //! Exposes a safe Rust API around libwdi's APIs
 
-use crate::ffi::{WdiDeviceInfo, WdiLogLevel, WdiOptionsCreateList, WdiOptionsPrepareDriver, WdiOptionsInstallDriver};
-use crate::ffi::{wdi_create_list, wdi_destroy_list, wdi_prepare_driver, wdi_install_driver, wdi_set_log_level};
-use std::ffi::{CStr, CString};
+use crate::ffi::{WdiDeviceInfo, WdiLogLevel, WdiOptionsCreateList, WdiOptionsPrepareDriver, WdiOptionsInstallDriver, WdiOptionsInstallCert};
+use crate::ffi::{wdi_create_list, wdi_destroy_list, wdi_prepare_driver, wdi_install_driver, wdi_install_trusted_certificate, wdi_set_log_level};
+use crate::ffi::{Guid, WDI_MAX_STRLEN};
+use crate::ffi::{DEVPKEY_DEVICE_MANUFACTURER, DEVPKEY_DEVICE_FRIENDLY_NAME, DEVPKEY_DEVICE_PARENT};
+use crate::ffi::{DEVPKEY_DEVICE_DRIVER_INF_PATH, DEVPKEY_DEVICE_DRIVER_VERSION, DEVPKEY_DEVICE_LOCATION_INFO};
+use crate::ffi::{DEVPKEY_DEVICE_DRIVER_DATE, DEVPKEY_DEVICE_DRIVER_PROVIDER, DEVPKEY_DEVICE_SERVICE};
+use crate::ffi::{DEVPKEY_DEVICE_DEV_NODE_STATUS, DEVPKEY_DEVICE_CONTAINER_ID, DN_STARTED, DN_HAS_PROBLEM};
+use crate::ffi::{wdi_get_vendor_name, wdi_get_wdf_version, wdi_is_driver_supported, wdi_is_file_embedded, wdi_strerror};
+use crate::setupapi::DeviceInfo;
+use crate::system::os_descriptor_cached;
+use crate::ring_log::warn;
+#[cfg(feature = "serde")]
+use crate::ring_log::error;
+use std::ffi::{c_void, CStr, CString};
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::os::raw::c_int;
 use std::ptr;
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 /// Log level for libwdi logging.  Note that libwdi is quite chatty, so the levels are shifted
 /// down by one when mapping the standard Rust log levels.
@@ -96,47 +113,220 @@ pub enum Error {
     Unsigned,
     Other,
     Unknown(c_int),
+    /// The requested driver is already installed for the device, so there
+    /// is nothing to do.  Not returned by libwdi itself.
+    AlreadyInstalled,
+    /// The device has an existing driver which the requested driver can't
+    /// replace - see [`DriverType::is_compatible_replacement_for`].  Not
+    /// returned by libwdi itself.
+    ConflictingDriver {
+        /// The name of the existing driver
+        current: String,
+    },
 }
 
 impl Error {
-    fn from_code(code: c_int) -> Result<(), Self> {
-        match code {
-            0 => Ok(()),
-            -1 => Err(Error::Io),
-            -2 => Err(Error::InvalidParam),
-            -3 => Err(Error::Access),
-            -4 => Err(Error::NoDevice),
-            -5 => Err(Error::NotFound),
-            -6 => Err(Error::Busy),
-            -7 => Err(Error::Timeout),
-            -8 => Err(Error::Overflow),
-            -9 => Err(Error::PendingInstallation),
-            -10 => Err(Error::Interrupted),
-            -11 => Err(Error::Resource),
-            -12 => Err(Error::NotSupported),
-            -13 => Err(Error::Exists),
-            -14 => Err(Error::UserCancel),
-            -15 => Err(Error::NeedsAdmin),
-            -16 => Err(Error::Wow64),
-            -17 => Err(Error::InfSyntax),
-            -18 => Err(Error::CatMissing),
-            -19 => Err(Error::Unsigned),
-            -99 => Err(Error::Other),
-            code => Err(Error::Unknown(code)),
+    /// All errors with a known libwdi error code.
+    const KNOWN: [Error; 20] = [
+        Error::Io, Error::InvalidParam, Error::Access, Error::NoDevice, Error::NotFound,
+        Error::Busy, Error::Timeout, Error::Overflow, Error::PendingInstallation,
+        Error::Interrupted, Error::Resource, Error::NotSupported, Error::Exists,
+        Error::UserCancel, Error::NeedsAdmin, Error::Wow64, Error::InfSyntax,
+        Error::CatMissing, Error::Unsigned, Error::Other,
+    ];
+
+    pub(crate) fn from_code(code: c_int) -> Result<(), Self> {
+        if code == 0 {
+            return Ok(());
+        }
+        
+        Err(Self::KNOWN.into_iter()
+            .find(|e| e.to_code() == code)
+            .unwrap_or(Error::Unknown(code)))
+    }
+
+    /// Converts a raw libwdi error code into an `Error`, for example one
+    /// previously serialized with [`code`](Error::code).
+    ///
+    /// Codes libwdi doesn't define yield [`Error::Unknown`].  0 is libwdi's
+    /// success code rather than an error, so isn't valid input - it yields
+    /// `Error::Unknown(0)`.
+    pub fn from_raw_code(code: i32) -> Error {
+        Self::from_code(code).err().unwrap_or(Error::Unknown(0))
+    }
+
+    /// Returns the raw libwdi error code for this error, for example for
+    /// telemetry, or to pass the error across a process boundary.
+    ///
+    /// This is the same as [`to_code`](Error::to_code).  The errors this
+    /// crate adds, which libwdi doesn't return itself, return the code of
+    /// the libwdi error they are a specific case of, so don't survive a round
+    /// trip through [`from_raw_code`](Error::from_raw_code).
+    pub fn code(&self) -> i32 {
+        self.to_code()
+    }
+
+    /// Returns the libwdi error code for this error.
+    pub fn to_code(&self) -> c_int {
+        match self {
+            Error::Io => -1,
+            Error::InvalidParam => -2,
+            Error::Access => -3,
+            Error::NoDevice => -4,
+            Error::NotFound => -5,
+            Error::Busy => -6,
+            Error::Timeout => -7,
+            Error::Overflow => -8,
+            Error::PendingInstallation => -9,
+            Error::Interrupted => -10,
+            Error::Resource => -11,
+            Error::NotSupported => -12,
+            Error::Exists => -13,
+            Error::UserCancel => -14,
+            Error::NeedsAdmin => -15,
+            Error::Wow64 => -16,
+            Error::InfSyntax => -17,
+            Error::CatMissing => -18,
+            Error::Unsigned => -19,
+            Error::Other => -99,
+            Error::Unknown(code) => *code,
+            // Both are specific cases of libwdi's "already exists"
+            Error::AlreadyInstalled | Error::ConflictingDriver { .. } => -13,
+        }
+    }
+
+    /// Returns libwdi's human-readable description of this error, e.g.
+    /// "The syntax of the SetupAPI definition file (INF) is invalid".
+    pub fn description(&self) -> &'static str {
+        match self {
+            Error::AlreadyInstalled => return "The driver is already installed",
+            Error::ConflictingDriver { .. } => return "A conflicting driver is already installed",
+            _ => {}
+        }
+        
+        let ptr = unsafe { wdi_strerror(self.to_code()) };
+        if ptr.is_null() {
+            return "Unknown error";
+        }
+        
+        // libwdi returns pointers to static strings
+        unsafe { CStr::from_ptr(ptr) }.to_str().unwrap_or("Unknown error")
+    }
+
+    /// Returns a suggested delay before retrying the failed operation, or
+    /// `None` if the error is not transient and retrying is unlikely to help.
+    ///
+    /// These are the crate's recommendations for generic retry loops:
+    /// - `Busy` and `Interrupted` - 500ms, as another operation is usually
+    ///   about to complete.
+    /// - `Resource` - 1s, to allow resources to be freed.
+    /// - `Timeout` - 2s.
+    /// - `PendingInstallation` - 10s, as Windows driver installations
+    ///   typically take tens of seconds to complete.
+    pub fn suggested_retry_delay(&self) -> Option<Duration> {
+        match self {
+            Error::Busy | Error::Interrupted => Some(Duration::from_millis(500)),
+            Error::Resource => Some(Duration::from_secs(1)),
+            Error::Timeout => Some(Duration::from_secs(2)),
+            Error::PendingInstallation => Some(Duration::from_secs(10)),
+            _ => None,
         }
     }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
+        write!(f, "{} ({:?})", self.description(), self)
     }
 }
 
 impl std::error::Error for Error {}
 
+/// A device interface GUID, as used by WinUSB to identify a device's
+/// interface to applications
+///
+/// Parse one from its string form, with or without braces:
+///
+/// ```no_run
+/// use wdi_rs::DeviceInterfaceGuid;
+///
+/// let guid: DeviceInterfaceGuid = "{6e45736a-2b1b-4078-b772-b3af2b6fde1c}".parse()?;
+/// println!("{}", guid);
+/// # Ok::<(), wdi_rs::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeviceInterfaceGuid {
+    data1: u32,
+    data2: u16,
+    data3: u16,
+    data4: [u8; 8],
+}
+
+impl DeviceInterfaceGuid {
+    pub(crate) fn to_ffi(self) -> Guid {
+        Guid {
+            data1: self.data1,
+            data2: self.data2,
+            data3: self.data3,
+            data4: self.data4,
+        }
+    }
+
+    pub(crate) fn from_ffi(guid: Guid) -> Self {
+        DeviceInterfaceGuid {
+            data1: guid.data1,
+            data2: guid.data2,
+            data3: guid.data3,
+            data4: guid.data4,
+        }
+    }
+}
+
+impl std::str::FromStr for DeviceInterfaceGuid {
+    type Err = Error;
+
+    /// Parses a GUID of the form `{xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx}`,
+    /// returning `Error::InvalidParam` if it is malformed.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let s = s.strip_prefix('{')
+            .and_then(|s| s.strip_suffix('}'))
+            .unwrap_or(s);
+
+        let parts: Vec<&str> = s.split('-').collect();
+        let lens = [8, 4, 4, 4, 12];
+        if parts.len() != lens.len()
+            || parts.iter().zip(lens).any(|(p, len)| p.len() != len || !p.chars().all(|c| c.is_ascii_hexdigit()))
+        {
+            return Err(Error::InvalidParam);
+        }
+
+        let hex = |s: &str| u64::from_str_radix(s, 16).map_err(|_| Error::InvalidParam);
+        let tail = hex(parts[3])? << 48 | hex(parts[4])?;
+
+        Ok(DeviceInterfaceGuid {
+            data1: hex(parts[0])? as u32,
+            data2: hex(parts[1])? as u16,
+            data3: hex(parts[2])? as u16,
+            data4: tail.to_be_bytes(),
+        })
+    }
+}
+
+impl fmt::Display for DeviceInterfaceGuid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let d = &self.data4;
+        write!(
+            f,
+            "{{{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}}}",
+            self.data1, self.data2, self.data3, d[0], d[1], d[2], d[3], d[4], d[5], d[6], d[7]
+        )
+    }
+}
+
 /// Driver types supported by libwdi
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DriverType {
     WinUsb,
     LibUsb0,
@@ -146,7 +336,13 @@ pub enum DriverType {
 }
 
 impl DriverType {
-    fn to_c_int(self) -> c_int {
+    /// All driver types, in libwdi's order.
+    const ALL: [DriverType; 5] = [
+        DriverType::WinUsb, DriverType::LibUsb0, DriverType::LibUsbK, DriverType::Cdc, DriverType::User,
+    ];
+
+    /// Returns libwdi's value for this driver type.
+    pub fn to_c_int(self) -> c_int {
         match self {
             DriverType::WinUsb => 0,
             DriverType::LibUsb0 => 1,
@@ -155,25 +351,188 @@ impl DriverType {
             DriverType::User => 4,
         }
     }
+
+    /// Converts libwdi's value for a driver type back into a `DriverType`,
+    /// the inverse of [`to_c_int`](DriverType::to_c_int).
+    ///
+    /// Returns `None` for values which aren't a driver type.
+    pub fn from_c_int(v: c_int) -> Option<DriverType> {
+        Self::ALL.into_iter().find(|t| t.to_c_int() == v)
+    }
+
+    /// Whether installing this driver type over a device's `current` driver
+    /// is a sensible replacement.
+    ///
+    /// The replacement matrix is:
+    ///
+    /// | Current driver                      | Replaceable by              |
+    /// |-------------------------------------|-----------------------------|
+    /// | [`DriverKind::None`]                | Any driver type             |
+    /// | WinUSB, libusb0, libusbK or usbser  | Any driver type except User |
+    /// | [`DriverKind::Other`]               | Nothing                     |
+    ///
+    /// Switching between the generic drivers libwdi installs (including
+    /// reinstalling the same one) is treated as a deliberate choice by the
+    /// caller.  Any other driver is likely a vendor or class function driver
+    /// which the device needs, so is never considered replaceable.  A
+    /// [`DriverType::User`] driver is only installed on devices with no driver,
+    /// as nothing is known about what it replaces.
+    pub fn is_compatible_replacement_for(&self, current: &DriverKind) -> bool {
+        match current {
+            DriverKind::None => true,
+            DriverKind::WinUsb | DriverKind::LibUsb0 | DriverKind::LibUsbK | DriverKind::Cdc => {
+                !matches!(self, DriverType::User)
+            }
+            DriverKind::Other(_) => false,
+        }
+    }
+
+    /// The [`DriverKind`] a device reports once this driver type is
+    /// installed, or `None` for [`DriverType::User`].
+    pub fn kind(&self) -> Option<DriverKind> {
+        match self {
+            DriverType::WinUsb => Some(DriverKind::WinUsb),
+            DriverType::LibUsb0 => Some(DriverKind::LibUsb0),
+            DriverType::LibUsbK => Some(DriverKind::LibUsbK),
+            DriverType::Cdc => Some(DriverKind::Cdc),
+            DriverType::User => None,
+        }
+    }
+}
+
+impl TryFrom<i32> for DriverType {
+    type Error = Error;
+
+    /// Converts libwdi's value for a driver type, returning
+    /// `Error::InvalidParam` for values which aren't a driver type - see
+    /// [`DriverType::from_c_int`].
+    fn try_from(v: i32) -> Result<Self, Self::Error> {
+        DriverType::from_c_int(v).ok_or(Error::InvalidParam)
+    }
+}
+
+/// The kind of driver currently bound to a device, classified from
+/// [`Device::driver`] - see [`Device::driver_kind`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DriverKind {
+    /// No driver is installed.
+    None,
+    WinUsb,
+    LibUsb0,
+    LibUsbK,
+    /// The USB serial (`usbser`) driver.
+    Cdc,
+    /// Any other driver, with its name.
+    Other(String),
+}
+
+impl DriverKind {
+    /// Classify a driver name, as reported by libwdi.  Names are matched
+    /// case-insensitively, and WinUSB by prefix, as its service may be
+    /// reported with a suffix.
+    pub fn from_driver(driver: Option<&str>) -> Self {
+        let Some(driver) = driver else {
+            return DriverKind::None;
+        };
+        
+        if driver.get(..6).is_some_and(|prefix| prefix.eq_ignore_ascii_case("WinUSB")) {
+            DriverKind::WinUsb
+        } else if driver.eq_ignore_ascii_case("libusb0") {
+            DriverKind::LibUsb0
+        } else if driver.eq_ignore_ascii_case("libusbK") {
+            DriverKind::LibUsbK
+        } else if driver.eq_ignore_ascii_case("usbser") {
+            DriverKind::Cdc
+        } else {
+            DriverKind::Other(driver.to_string())
+        }
+    }
+}
+
+/// A Windows driver version, as `major.minor.build.revision`, unpacked from
+/// [`Device::driver_version`] - see [`Device::version`].
+///
+/// Versions order as Windows compares them, by major version first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DriverVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub build: u16,
+    pub revision: u16,
+}
+
+impl From<u64> for DriverVersion {
+    fn from(version: u64) -> Self {
+        DriverVersion {
+            major: (version >> 48) as u16,
+            minor: (version >> 32) as u16,
+            build: (version >> 16) as u16,
+            revision: version as u16,
+        }
+    }
+}
+
+impl From<DriverVersion> for u64 {
+    fn from(version: DriverVersion) -> Self {
+        (u64::from(version.major) << 48)
+            | (u64::from(version.minor) << 32)
+            | (u64::from(version.build) << 16)
+            | u64::from(version.revision)
+    }
+}
+
+impl fmt::Display for DriverVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}.{}", self.major, self.minor, self.build, self.revision)
+    }
 }
 
 /// Represents a connected device.  The fields correspond to those returned by libwdi
-#[derive(Debug, Clone)]
+///
+/// Devices compare equal, and hash the same, if their `vid`, `pid`, `mi` and
+/// `device_id` match.  The other fields, such as `driver` and
+/// `driver_version`, can change between enumerations, so two enumerations of
+/// the same physical device compare equal even after its driver changes.
+///
+/// libwdi takes the string fields as NUL-terminated C strings, and supports
+/// strings of up to [`Device::MAX_STRLEN`] bytes, including the NUL.  When the
+/// device is passed to [`prepare_driver`] or [`install_driver`], a longer
+/// `desc` is truncated, and any other longer field is rejected with
+/// `Error::InvalidParam`.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Device {
     pub vid: u16,
     pub pid: u16,
     pub is_composite: bool,
     pub mi: u8,
+    /// The device's description - truncated to [`Device::MAX_STRLEN`] - 1
+    /// bytes when passed to libwdi
     pub desc: Option<String>,
+    /// The device's driver - must be at most [`Device::MAX_STRLEN`] - 1 bytes
     pub driver: Option<String>,
+    /// The device instance ID - must be at most [`Device::MAX_STRLEN`] - 1 bytes
     pub device_id: Option<String>,
+    /// The hardware ID - must be at most [`Device::MAX_STRLEN`] - 1 bytes
     pub hardware_id: Option<String>,
+    /// The compatible ID - must be at most [`Device::MAX_STRLEN`] - 1 bytes
     pub compatible_id: Option<String>,
+    /// Any upper filter drivers - must be at most [`Device::MAX_STRLEN`] - 1 bytes
     pub upper_filter: Option<String>,
     pub driver_version: u64,
 }
 
 impl Device {
+    /// The longest string libwdi supports, including the terminating NUL -
+    /// libwdi's `WDI_MAX_STRLEN`.
+    pub const MAX_STRLEN: usize = WDI_MAX_STRLEN;
+
+    /// The container ID Windows assigns to devices which are part of the
+    /// computer itself, rather than a separate physical device - see
+    /// [`container_id`](Device::container_id).
+    pub const NULL_CONTAINER_ID: &'static str = "{00000000-0000-0000-FFFF-FFFFFFFFFFFF}";
+
     unsafe fn from_raw(raw: *const WdiDeviceInfo) -> Self {
         let raw = unsafe { &*raw };
         Device {
@@ -181,7 +540,7 @@ impl Device {
             pid: raw.pid,
             is_composite: raw.is_composite != 0,
             mi: raw.mi,
-            desc: unsafe{ ptr_to_string(raw.desc) },
+            desc: unsafe{ ptr_to_string(raw.desc) }.filter(|desc| !desc.is_empty()),
             driver: unsafe{ ptr_to_string(raw.driver) },
             device_id: unsafe{ ptr_to_string(raw.device_id) },
             hardware_id: unsafe{ ptr_to_string(raw.hardware_id) },
@@ -190,6 +549,414 @@ impl Device {
             driver_version: raw.driver_version,
         }
     }
+
+    /// Overwrites this device with the contents of `raw`, reusing the
+    /// existing string allocations where possible.
+    unsafe fn update_from_raw(&mut self, raw: *const WdiDeviceInfo) {
+        let raw = unsafe { &*raw };
+        self.vid = raw.vid;
+        self.pid = raw.pid;
+        self.is_composite = raw.is_composite != 0;
+        self.mi = raw.mi;
+        unsafe {
+            assign_ptr_string(&mut self.desc, raw.desc);
+            if self.desc.as_deref() == Some("") {
+                self.desc = None;
+            }
+            assign_ptr_string(&mut self.driver, raw.driver);
+            assign_ptr_string(&mut self.device_id, raw.device_id);
+            assign_ptr_string(&mut self.hardware_id, raw.hardware_id);
+            assign_ptr_string(&mut self.compatible_id, raw.compatible_id);
+            assign_ptr_string(&mut self.upper_filter, raw.upper_filter);
+        }
+        self.driver_version = raw.driver_version;
+    }
+
+    /// The device's description, treating an empty description as none.
+    ///
+    /// Some devices report an empty description rather than none.  Devices
+    /// enumerated by this crate have these normalized to `None`, but this
+    /// also handles devices constructed or modified elsewhere.
+    pub fn effective_desc(&self) -> Option<&str> {
+        self.desc.as_deref().filter(|desc| !desc.is_empty())
+    }
+
+    /// The kind of driver currently bound to this device.
+    pub fn driver_kind(&self) -> DriverKind {
+        DriverKind::from_driver(self.driver.as_deref())
+    }
+
+    /// Returns the known driver types currently installed on this device,
+    /// best-effort.
+    ///
+    /// This classifies the service Windows has bound to the device (falling
+    /// back to libwdi's `driver` if Windows can't be queried) and any upper
+    /// filter driver, such as libusb0 installed as a filter.  Drivers which
+    /// aren't one of the [`DriverType`]s are omitted, so this is empty for a
+    /// device with no driver or only a vendor driver.  For a composite
+    /// device, call this on each interface to see each function's driver.
+    pub fn installed_driver_types(&self) -> Vec<DriverType> {
+        let service = self.device_id.as_deref()
+            .and_then(DeviceInfo::open)
+            .and_then(|info| info.property_string(&DEVPKEY_DEVICE_SERVICE))
+            .or_else(|| self.driver.clone());
+
+        let mut types = Vec::new();
+        let filters = self.upper_filter.iter().flat_map(|f| f.split([';', ',']));
+        for name in service.iter().map(String::as_str).chain(filters) {
+            let driver_type = match DriverKind::from_driver(Some(name.trim())) {
+                DriverKind::WinUsb => DriverType::WinUsb,
+                DriverKind::LibUsb0 => DriverType::LibUsb0,
+                DriverKind::LibUsbK => DriverType::LibUsbK,
+                DriverKind::Cdc => DriverType::Cdc,
+                DriverKind::None | DriverKind::Other(_) => continue,
+            };
+            if !types.contains(&driver_type) {
+                types.push(driver_type);
+            }
+        }
+        types
+    }
+
+    /// Queries Windows for the device's manufacturer string
+    /// (`DEVPKEY_Device_Manufacturer`).
+    ///
+    /// libwdi's `desc` often omits or combines the manufacturer, so this is
+    /// useful for matching or displaying it independently.  Returns `None` if
+    /// the device has no `device_id`, is no longer known to Windows, or has no
+    /// manufacturer set.
+    pub fn manufacturer(&self) -> Option<String> {
+        DeviceInfo::open(self.device_id.as_deref()?)?
+            .property_string(&DEVPKEY_DEVICE_MANUFACTURER)
+    }
+
+    /// Queries Windows for the device's friendly name
+    /// (`DEVPKEY_Device_FriendlyName`), as shown in Device Manager.
+    ///
+    /// This often differs from libwdi's `desc`, for example including the
+    /// COM port for a serial device.  Returns `None` if the device has no
+    /// `device_id`, is no longer known to Windows, or has no friendly name -
+    /// in which case Device Manager shows its description instead.
+    pub fn friendly_name(&self) -> Option<String> {
+        DeviceInfo::open(self.device_id.as_deref()?)?
+            .property_string(&DEVPKEY_DEVICE_FRIENDLY_NAME)
+            .filter(|name| !name.is_empty())
+    }
+
+    /// Queries Windows for the COM port assigned to this device, e.g. `COM3`.
+    ///
+    /// This is typically used after installing the [`DriverType::Cdc`] driver,
+    /// to find the port the device can be opened on.  The port is read from
+    /// the device's `PortName` registry value, falling back to parsing the
+    /// Device Manager friendly name.  Returns `None` for devices without a
+    /// serial function.
+    pub fn com_port(&self) -> Option<String> {
+        let info = DeviceInfo::open(self.device_id.as_deref()?)?;
+        info.registry_string("PortName")
+            .filter(|port| !port.is_empty())
+            .or_else(|| {
+                info.property_string(&DEVPKEY_DEVICE_FRIENDLY_NAME)
+                    .and_then(|name| com_port_from_friendly_name(&name))
+            })
+    }
+
+    /// Queries Windows for the device's location, e.g.
+    /// `Port_#0002.Hub_#0001`.
+    pub(crate) fn location(&self) -> Option<String> {
+        DeviceInfo::open(self.device_id.as_deref()?)?
+            .property_string(&DEVPKEY_DEVICE_LOCATION_INFO)
+    }
+
+    /// Returns `true` if the device has a working function driver - a driver
+    /// is bound, the device has started without a problem, and the driver
+    /// isn't a generic one which doesn't provide the device's function, such
+    /// as the USB generic parent driver.
+    ///
+    /// Devices for which this returns `false` are driverless, unknown or
+    /// failed devices, which are the likely candidates for installing a
+    /// driver - see [`DeviceList::needing_driver`].  This queries Windows for
+    /// the device's status, and returns `false` if the device has no
+    /// `device_id` or is no longer known to Windows.
+    pub fn has_functional_driver(&self) -> bool {
+        let Some(driver) = self.driver.as_deref().filter(|d| !d.is_empty()) else {
+            return false;
+        };
+        if GENERIC_DRIVERS.iter().any(|generic| generic.eq_ignore_ascii_case(driver)) {
+            return false;
+        }
+
+        let Some(info) = self.device_id.as_deref().and_then(DeviceInfo::open) else {
+            return false;
+        };
+        let status = info.property_u32(&DEVPKEY_DEVICE_DEV_NODE_STATUS).unwrap_or(0);
+        status & DN_STARTED != 0 && status & DN_HAS_PROBLEM == 0
+    }
+
+    /// Returns the version of the device's current driver, unpacked from
+    /// [`driver_version`](Device::driver_version).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::{create_list, CreateListOptions, DriverVersion};
+    ///
+    /// let mine = DriverVersion { major: 6, minor: 1, build: 7600, revision: 16385 };
+    /// for device in create_list(CreateListOptions::default())?.iter() {
+    ///     if device.version() < mine {
+    ///         println!("{} has older driver {}", device, device.version());
+    ///     }
+    /// }
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn version(&self) -> DriverVersion {
+        DriverVersion::from(self.driver_version)
+    }
+
+    /// Queries Windows for the published INF name (e.g. `oem12.inf`) of the
+    /// device's current driver.
+    pub(crate) fn driver_inf_name(&self) -> Option<String> {
+        DeviceInfo::open(self.device_id.as_deref()?)?
+            .property_string(&DEVPKEY_DEVICE_DRIVER_INF_PATH)
+    }
+
+    /// Queries Windows for the version of the device's current driver.
+    pub(crate) fn driver_version_string(&self) -> Option<String> {
+        DeviceInfo::open(self.device_id.as_deref()?)?
+            .property_string(&DEVPKEY_DEVICE_DRIVER_VERSION)
+    }
+
+    /// Queries Windows for the provider of the device's current driver, e.g.
+    /// `Microsoft` or `libwdi`.
+    ///
+    /// Together with [`driver_date`](Device::driver_date), this is useful for
+    /// comparing the installed driver with a candidate replacement.  Returns
+    /// `None` if the device has no `device_id`, no driver, or is no longer
+    /// known to Windows.
+    pub fn driver_provider(&self) -> Option<String> {
+        DeviceInfo::open(self.device_id.as_deref()?)?
+            .property_string(&DEVPKEY_DEVICE_DRIVER_PROVIDER)
+    }
+
+    /// Queries Windows for the date of the device's current driver, as
+    /// `(year, month, day)`.
+    ///
+    /// This is the date from the driver's INF `DriverVer` directive.  Returns
+    /// `None` if the device has no `device_id`, no driver, or is no longer
+    /// known to Windows.
+    pub fn driver_date(&self) -> Option<(u16, u16, u16)> {
+        DeviceInfo::open(self.device_id.as_deref()?)?
+            .property_date(&DEVPKEY_DEVICE_DRIVER_DATE)
+    }
+
+    /// Queries Windows for the device's container ID
+    /// (`DEVPKEY_Device_ContainerId`), in the form
+    /// `{XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX}`.
+    ///
+    /// Windows assigns the same container ID to every function of a single
+    /// physical device, so this is the authoritative way to group the
+    /// interfaces of a composite device - even where the device presents
+    /// several VIDs, for example via an internal hub.  Devices Windows treats
+    /// as part of the computer itself all share the
+    /// [`NULL_CONTAINER_ID`](Device::NULL_CONTAINER_ID).
+    ///
+    /// Returns `None` if the device has no `device_id`, or is no longer known
+    /// to Windows.
+    pub fn container_id(&self) -> Option<String> {
+        DeviceInfo::open(self.device_id.as_deref()?)?
+            .property_guid(&DEVPKEY_DEVICE_CONTAINER_ID)
+            .map(|guid| DeviceInterfaceGuid::from_ffi(guid).to_string())
+    }
+
+    /// Returns a stable key identifying this device across enumerations, for
+    /// example to cache per-device state in a `HashMap`.
+    ///
+    /// The key is the device's [container ID](Device::container_id), VID,
+    /// PID and interface number if Windows reports a container ID, otherwise
+    /// its `device_id`, otherwise its VID, PID and interface number.  Devices
+    /// which are part of the computer itself share the
+    /// [`NULL_CONTAINER_ID`](Device::NULL_CONTAINER_ID), so fall back to
+    /// their `device_id`.  Keys are compared case-insensitively.
+    ///
+    /// Keys aren't guaranteed to be unique.  Two functions with the same VID,
+    /// PID and interface number in one physical device - for example
+    /// identical devices built into a dock - share a container ID, and so a
+    /// key.  Callers keying a map on this should handle collisions, for
+    /// example by falling back to the `device_id`.
+    ///
+    /// Querying the container ID means this asks Windows about the device,
+    /// so compute the key once per enumeration rather than on every lookup.
+    pub fn key(&self) -> DeviceKey {
+        self.key_in_container(self.container_id())
+    }
+
+    /// The key for this device ignoring its container ID - its `device_id`,
+    /// or VID, PID and interface number - for callers to fall back to when
+    /// [`key`](Device::key)s collide.
+    pub(crate) fn device_id_key(&self) -> DeviceKey {
+        self.key_in_container(None)
+    }
+
+    /// The key for this device, given its container ID.
+    fn key_in_container(&self, container: Option<String>) -> DeviceKey {
+        let mi = self.is_composite.then_some(self.mi);
+        let id = match (container, &self.device_id) {
+            (Some(container), _) if container != Self::NULL_CONTAINER_ID => {
+                DeviceKeyId::Container {
+                    id: container.to_ascii_uppercase(),
+                    vid: self.vid,
+                    pid: self.pid,
+                    mi,
+                }
+            }
+            (_, Some(device_id)) => DeviceKeyId::DeviceId(device_id.to_ascii_uppercase()),
+            (_, None) => DeviceKeyId::Usb { vid: self.vid, pid: self.pid, mi },
+        };
+        DeviceKey(id)
+    }
+
+    /// Returns whether the device advertises a Microsoft OS descriptor, which
+    /// WCID devices use to have Windows bind a driver automatically - see
+    /// [`PrepareDriverOptions::use_wcid_driver`].
+    ///
+    /// This is best-effort: it reads the result Windows caches in the
+    /// registry the first time it enumerates a device.  Returns `None` if
+    /// Windows hasn't cached a result for the device, or its revision can't
+    /// be determined from its `hardware_id`.
+    pub fn supports_wcid(&self) -> Option<bool> {
+        let rev = revision_from_hardware_id(self.hardware_id.as_deref()?)?;
+        os_descriptor_cached(self.vid, self.pid, rev)
+    }
+
+    /// Queries Windows for the device instance ID of this device's parent.
+    pub(crate) fn parent_id(&self) -> Option<String> {
+        DeviceInfo::open(self.device_id.as_deref()?)?
+            .property_string(&DEVPKEY_DEVICE_PARENT)
+    }
+
+    /// Returns the USB serial number of this device, if it has one.
+    ///
+    /// This is taken from the device instance ID, which for devices with a
+    /// serial number ends with it, e.g. `USB\VID_1234&PID_5678\ABC123`.  For
+    /// an interface of a composite device, the parent device's instance ID is
+    /// queried.  Returns `None` if the device has no serial number, in which
+    /// case Windows generates an instance ID containing `&` instead.
+    pub fn serial_number(&self) -> Option<String> {
+        let id = if self.is_composite {
+            self.parent_id()?
+        } else {
+            self.device_id.clone()?
+        };
+        
+        serial_from_instance_id(&id).map(str::to_string)
+    }
+
+    /// Explains why libwdi does or doesn't treat this device as an interface
+    /// of a composite device.
+    ///
+    /// libwdi lists each interface of a composite device as a separate
+    /// [`Device`], with `is_composite` set and `mi` holding the interface
+    /// number.  For these, this returns the parent (composite) device's
+    /// instance ID and how many of its interfaces are in `devices`, which
+    /// should be an enumeration of all devices, such as from
+    /// [`create_list`] with [`CreateListOptions::all`].  Enumerate once and
+    /// pass the same devices for each device examined.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::{create_list, CreateListOptions};
+    ///
+    /// let devices = create_list(CreateListOptions::all())?.into_vec();
+    /// for device in &devices {
+    ///     println!("{}: {:?}", device, device.composite_detail(&devices));
+    /// }
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn composite_detail(&self, devices: &[Device]) -> CompositeDetail {
+        if !self.is_composite {
+            return CompositeDetail::NotComposite;
+        }
+
+        let parent_id = self.parent_id();
+        let interface_count = devices.iter()
+            .filter(|d| d.is_composite && d.vid == self.vid && d.pid == self.pid)
+            .filter(|d| parent_id.is_none() || d.parent_id() == parent_id)
+            .count();
+
+        CompositeDetail::Composite {
+            mi: self.mi,
+            parent_id,
+            interface_count,
+        }
+    }
+}
+
+/// Extract the revision (`bcdDevice`) from a USB hardware ID, e.g.
+/// `USB\VID_1234&PID_5678&REV_0100`.
+fn revision_from_hardware_id(id: &str) -> Option<u16> {
+    id.split(['\\', '&'])
+        .find_map(|part| part.get(..4).filter(|p| p.eq_ignore_ascii_case("REV_")).map(|_| &part[4..]))
+        .and_then(|rev| u16::from_str_radix(rev, 16).ok())
+}
+
+/// Extract the serial number from a USB device instance ID.
+pub(crate) fn serial_from_instance_id(id: &str) -> Option<&str> {
+    let (prefix, serial) = id.rsplit_once('\\')?;
+    if !prefix.to_ascii_uppercase().starts_with("USB\\") || serial.is_empty() || serial.contains('&') {
+        return None;
+    }
+    Some(serial)
+}
+
+/// Drivers which bind a device without providing its function, so don't
+/// count as a working driver for [`Device::has_functional_driver`].
+const GENERIC_DRIVERS: &[&str] = &["usbccgp"];
+
+/// Details of a device's composite status, as returned by
+/// [`Device::composite_detail`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompositeDetail {
+    /// The device is not an interface of a composite device
+    NotComposite,
+
+    /// The device is one interface of a composite device
+    Composite {
+        /// The interface number of this device
+        mi: u8,
+        /// The device instance ID of the parent composite device, if it could
+        /// be determined
+        parent_id: Option<String>,
+        /// The number of the parent's interfaces in the devices passed to
+        /// [`Device::composite_detail`]
+        interface_count: usize,
+    },
+}
+
+/// Extracts the port from a friendly name like `USB Serial Device (COM3)`.
+fn com_port_from_friendly_name(name: &str) -> Option<String> {
+    let start = name.rfind("(COM")? + 1;
+    let end = start + name[start..].find(')')?;
+    Some(name[start..end].to_string())
+}
+
+impl PartialEq for Device {
+    fn eq(&self, other: &Self) -> bool {
+        self.vid == other.vid
+            && self.pid == other.pid
+            && self.mi == other.mi
+            && self.device_id == other.device_id
+    }
+}
+
+impl Eq for Device {}
+
+impl Hash for Device {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.vid.hash(state);
+        self.pid.hash(state);
+        self.mi.hash(state);
+        self.device_id.hash(state);
+    }
 }
 
 impl std::fmt::Display for Device {
@@ -199,11 +966,32 @@ impl std::fmt::Display for Device {
             "{:04X}:{:04X} {}",
             self.vid,
             self.pid,
-            self.desc.as_deref().unwrap_or("(no description)")
+            self.effective_desc().unwrap_or("(no description)")
         )
     }
 }
 
+/// A non-composite device with the given VID and PID, and no other details,
+/// for tests to override with struct update syntax.
+#[cfg(test)]
+pub(crate) fn test_device(vid: u16, pid: u16) -> Device {
+    Device { vid, pid, ..Device::default() }
+}
+
+/// A stable, hashable identity for a device, which survives
+/// re-enumeration - see [`Device::key`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DeviceKey(DeviceKeyId);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum DeviceKeyId {
+    /// Container ID, with the VID, PID and, for a composite device, the
+    /// interface number to distinguish the functions within the container
+    Container { id: String, vid: u16, pid: u16, mi: Option<u8> },
+    DeviceId(String),
+    Usb { vid: u16, pid: u16, mi: Option<u8> },
+}
+
 unsafe fn ptr_to_string(ptr: *mut i8) -> Option<String> {
     if ptr.is_null() {
         None
@@ -212,15 +1000,155 @@ unsafe fn ptr_to_string(ptr: *mut i8) -> Option<String> {
     }
 }
 
+/// As [`ptr_to_string`], but reuses `dst`'s existing allocation if it has one.
+unsafe fn assign_ptr_string(dst: &mut Option<String>, ptr: *mut i8) {
+    let src = if ptr.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(ptr).to_str().ok() }
+    };
+
+    match (src, dst.as_mut()) {
+        (Some(src), Some(dst)) => {
+            dst.clear();
+            dst.push_str(src);
+        }
+        (Some(src), None) => *dst = Some(src.to_owned()),
+        (None, _) => *dst = None,
+    }
+}
+
+/// A physical device, with its interfaces, as returned by
+/// [`DeviceList::for_picker`]
+#[derive(Debug, Clone)]
+pub struct PickerEntry {
+    /// A name to show for the device - its first interface's description,
+    /// or its VID and PID if it has none
+    pub name: String,
+    /// USB Vendor ID, of the first interface
+    pub vid: u16,
+    /// USB Product ID, of the first interface
+    pub pid: u16,
+    /// The vendor name for the device's VID, from libwdi's database
+    pub vendor_name: Option<String>,
+    /// The device's container ID, if it was grouped by one
+    pub container_id: Option<String>,
+    /// Whether any interface lacks a working function driver - see
+    /// [`Device::has_functional_driver`]
+    pub needs_driver: bool,
+    /// The device's interfaces, in interface number order, or the single
+    /// device if it isn't composite.  Use [`Device::driver_kind`] for each
+    /// interface's current driver.
+    pub interfaces: Vec<Device>,
+}
+
+impl PickerEntry {
+    fn new(interfaces: Vec<Device>) -> Self {
+        let first = &interfaces[0];
+        PickerEntry {
+            name: first.effective_desc()
+                .map_or_else(|| format!("USB device {:04X}:{:04X}", first.vid, first.pid), str::to_string),
+            vid: first.vid,
+            pid: first.pid,
+            vendor_name: get_vendor_name(first.vid),
+            container_id: first.container_id()
+                .filter(|id| !id.eq_ignore_ascii_case(Device::NULL_CONTAINER_ID)),
+            needs_driver: interfaces.iter().any(|d| !d.has_functional_driver()),
+            interfaces,
+        }
+    }
+}
+
+/// What groups devices into a [`PickerEntry`].
+#[derive(Debug, PartialEq)]
+enum PickerKey {
+    Container(String),
+    Composite { vid: u16, pid: u16, parent_id: String },
+}
+
+impl PickerKey {
+    /// The key for a device, or `None` if it is a physical device of its own.
+    fn for_device(device: &Device) -> Option<Self> {
+        if let Some(id) = device.container_id()
+            .filter(|id| !id.eq_ignore_ascii_case(Device::NULL_CONTAINER_ID))
+        {
+            return Some(PickerKey::Container(id));
+        }
+
+        device.is_composite.then(|| device.parent_id()).flatten().map(|parent_id| PickerKey::Composite {
+            vid: device.vid,
+            pid: device.pid,
+            parent_id,
+        })
+    }
+}
+
+/// Group devices with the same key, in order of first appearance, sorting
+/// each group by interface number.  Devices without a key are each in a
+/// group of their own.
+fn group_by_key<K: PartialEq>(
+    devices: impl IntoIterator<Item = Device>,
+    key: impl Fn(&Device) -> Option<K>,
+) -> Vec<Vec<Device>> {
+    let mut groups: Vec<(Option<K>, Vec<Device>)> = Vec::new();
+    for device in devices {
+        let device_key = key(&device);
+        let existing = device_key.as_ref()
+            .and_then(|k| groups.iter().position(|(g, _)| g.as_ref() == Some(k)));
+        match existing {
+            Some(index) => groups[index].1.push(device),
+            None => groups.push((device_key, vec![device])),
+        }
+    }
+
+    groups.into_iter()
+        .map(|(_, mut group)| {
+            group.sort_by_key(|d| (d.mi, d.vid, d.pid));
+            group
+        })
+        .collect()
+}
+
 /// Represents a list of connected devices
 /// 
 /// Use the [`iter`](DeviceList::iter) method to iterate over the devices
+///
+/// # Threading
+///
+/// A `DeviceList` is [`Send`], so can be created on a background thread and
+/// moved to another, such as a UI thread.  It isn't [`Sync`] - to share the
+/// devices between threads, convert it with [`into_vec`](DeviceList::into_vec)
+/// instead, as [`Device`] owns all of its fields and is both `Send` and `Sync`.
 #[derive(Debug)]
 pub struct DeviceList {
+    // Either null, or the head of a list returned by a successful
+    // `wdi_create_list`, owned exclusively by this struct and freed exactly
+    // once, on drop.  `Device`s copy their strings out of the list, so none
+    // borrow from it.
     head: *mut WdiDeviceInfo,
 }
 
+// SAFETY: The list is exclusively owned by this struct, and freed only on
+// drop.  libwdi doesn't touch it after `wdi_create_list` returns, nor tie it
+// to the thread which created it, so it can be moved between threads.
+unsafe impl Send for DeviceList {}
+
 impl DeviceList {
+    /// Takes ownership of the list from a `wdi_create_list` call, if it
+    /// succeeded.
+    ///
+    /// On failure, `head` is neither read nor freed, whatever its value, and
+    /// no list is constructed - libwdi may already have freed any partial
+    /// list, so freeing it here would risk a double free.
+    ///
+    /// # Safety
+    /// If `result` is success, `head` must be null or the head of the list
+    /// `wdi_create_list` returned, not owned by anything else.
+    unsafe fn from_create_result(result: c_int, head: *mut WdiDeviceInfo) -> Result<Self, Error> {
+        Error::from_code(result)?;
+        Ok(DeviceList { head })
+    }
+
     /// Returns an iterator over the devices in the list
     pub fn iter(&self) -> DeviceIter {
         DeviceIter {
@@ -228,6 +1156,15 @@ impl DeviceList {
         }
     }
 
+    /// Returns an iterator over the devices in the list, paired with each
+    /// device's index in libwdi's original enumeration order
+    ///
+    /// This mirrors [`Iterator::enumerate`], and is useful for recording a
+    /// device's original position before sorting or filtering.
+    pub fn enumerate(&self) -> impl Iterator<Item = (usize, Device)> {
+        self.iter().enumerate()
+    }
+
     /// Gets the number of devices in the list
     pub fn len(&self) -> usize {
         self.iter().count()
@@ -249,6 +1186,128 @@ impl DeviceList {
             .filter(|d| d.vid == vid && d.pid == pid)
             .collect()
     }
+
+    /// Finds the first device with the given VID and PID, without collecting
+    /// every match as [`from_vid_pid`](DeviceList::from_vid_pid) does
+    pub fn find_vid_pid(&self, vid: u16, pid: u16) -> Option<Device> {
+        self.find(|d| d.vid == vid && d.pid == pid)
+    }
+
+    /// Finds the first device matching a predicate, as
+    /// [`DeviceSelector::First`](crate::DeviceSelector::First) selects one
+    pub fn find(&self, predicate: impl Fn(&Device) -> bool) -> Option<Device> {
+        self.iter().find(|d| predicate(d))
+    }
+
+    /// Filters the device list by VID alone, returning every device from a
+    /// vendor's product line, whatever its PID
+    pub fn by_vid(&self, vid: u16) -> Vec<Device> {
+        self.iter()
+            .filter(|d| d.vid == vid)
+            .collect()
+    }
+
+    /// Filters the device list to devices matching any of the given
+    /// (VID, PID) pairs, for example a family of products
+    pub fn matching_any(&self, pairs: &[(u16, u16)]) -> Vec<Device> {
+        self.iter()
+            .filter(|d| pairs.contains(&(d.vid, d.pid)))
+            .collect()
+    }
+
+    /// Filters the device list to devices with no driver
+    pub fn without_driver(&self) -> Vec<Device> {
+        self.iter()
+            .filter(|d| d.driver.is_none())
+            .collect()
+    }
+
+    /// Filters the device list to devices whose driver name starts with
+    /// `name_prefix`, compared case-insensitively - for example `"WinUSB"`
+    pub fn with_driver(&self, name_prefix: &str) -> Vec<Device> {
+        self.iter()
+            .filter(|d| d.driver.as_deref().is_some_and(|driver| {
+                driver.get(..name_prefix.len()).is_some_and(|p| p.eq_ignore_ascii_case(name_prefix))
+            }))
+            .collect()
+    }
+
+    /// Filters the device list to devices without a working function driver,
+    /// which are the likely candidates for installing a driver - see
+    /// [`Device::has_functional_driver`]
+    ///
+    /// This suits a "needs attention" filter in a device picker, so users
+    /// aren't offered to replace drivers on devices which already work.
+    pub fn needing_driver(&self) -> Vec<Device> {
+        self.iter()
+            .filter(|d| !d.has_functional_driver())
+            .collect()
+    }
+
+    /// Groups the devices into one entry per physical device, for display in
+    /// a device picker
+    ///
+    /// Each interface of a composite device is enumerated as a separate
+    /// device, cluttering pickers with near-identical rows.  Here devices
+    /// are grouped by their [container ID](Device::container_id), falling
+    /// back to grouping composite interfaces by VID, PID and parent device.
+    /// Entries are in enumeration order, and each carries its devices, so a
+    /// chosen entry can be passed straight to
+    /// [`DriverInstaller::for_specific_device`](crate::DriverInstaller::for_specific_device).
+    ///
+    /// This queries Windows for each device, so is slower than iterating
+    /// over the list.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::{create_list, CreateListOptions};
+    ///
+    /// let devices = create_list(CreateListOptions::default())?;
+    /// for entry in devices.for_picker() {
+    ///     println!(
+    ///         "{} ({}) - {} interface(s){}",
+    ///         entry.name,
+    ///         entry.vendor_name.as_deref().unwrap_or("unknown vendor"),
+    ///         entry.interfaces.len(),
+    ///         if entry.needs_driver { " - needs driver" } else { "" },
+    ///     );
+    /// }
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn for_picker(&self) -> Vec<PickerEntry> {
+        group_by_key(self.iter(), PickerKey::for_device)
+            .into_iter()
+            .map(PickerEntry::new)
+            .collect()
+    }
+
+    /// Converts the list into a vector of owned [`Device`]s
+    ///
+    /// The devices are copied out of libwdi's list, which is then freed.
+    pub fn into_vec(self) -> Vec<Device> {
+        self.iter().collect()
+    }
+}
+
+impl IntoIterator for DeviceList {
+    type Item = Device;
+    type IntoIter = std::vec::IntoIter<Device>;
+
+    /// Returns an iterator over owned [`Device`]s, freeing libwdi's list
+    /// first - see [`into_vec`](DeviceList::into_vec).
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_vec().into_iter()
+    }
+}
+
+impl IntoIterator for &DeviceList {
+    type Item = Device;
+    type IntoIter = DeviceIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
 }
 
 impl Drop for DeviceList {
@@ -284,6 +1343,7 @@ impl Iterator for DeviceIter {
 
 /// Options for creating a device list, as exposed by libwdi
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CreateListOptions {
     pub list_all: bool,
     pub list_hubs: bool,
@@ -300,6 +1360,40 @@ impl Default for CreateListOptions {
     }
 }
 
+impl CreateListOptions {
+    /// Options listing all devices, including those which already have a
+    /// driver, with whitespace trimmed - the common case when looking for a
+    /// device to install a driver for.
+    ///
+    /// Adjust with the other builder methods, for example:
+    ///
+    /// ```no_run
+    /// use wdi_rs::{create_list, CreateListOptions};
+    ///
+    /// let devices = create_list(CreateListOptions::all().with_hubs(true))?;
+    /// # Ok::<(), wdi_rs::Error>(())
+    /// ```
+    pub fn all() -> Self {
+        CreateListOptions {
+            list_all: true,
+            ..CreateListOptions::default()
+        }
+    }
+
+    /// Sets whether to include USB hubs.
+    pub fn with_hubs(mut self, list_hubs: bool) -> Self {
+        self.list_hubs = list_hubs;
+        self
+    }
+
+    /// Sets whether to trim leading and trailing whitespace from device
+    /// descriptions.
+    pub fn trim(mut self, trim_whitespaces: bool) -> Self {
+        self.trim_whitespaces = trim_whitespaces;
+        self
+    }
+}
+
 /// Enumerates connected devices and returns a [`DeviceList`]
 /// 
 /// # Arguments
@@ -315,10 +1409,262 @@ pub fn create_list(options: CreateListOptions) -> Result<DeviceList, Error> {
     
     unsafe {
         let result = wdi_create_list(&mut list, &mut opts);
-        Error::from_code(result)?;
+        DeviceList::from_create_result(result, list)
+    }
+}
+
+/// How often [`create_list_cancelable`] checks its cancellation token.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Enumerates connected devices as [`create_list`], but can be cancelled
+/// using `token`.
+///
+/// Enumeration runs on a worker thread, and if `token` is set to `true`
+/// before it completes, this returns [`Error::UserCancel`] immediately.  This
+/// lets a UI abandon a slow "scanning for devices" operation, for example
+/// when the user navigates away.
+///
+/// libwdi's enumeration can't itself be interrupted, so after cancellation
+/// the underlying call still runs to completion in the background, and its
+/// result is discarded.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use wdi_rs::{create_list_cancelable, CreateListOptions};
+///
+/// let token = Arc::new(AtomicBool::new(false));
+///
+/// // Set from elsewhere, e.g. the UI thread, to abandon the scan
+/// let cancel = token.clone();
+/// # cancel.store(false, Ordering::Relaxed);
+///
+/// let devices = create_list_cancelable(CreateListOptions::default(), token)?;
+/// # Ok::<(), wdi_rs::Error>(())
+/// ```
+pub fn create_list_cancelable(options: CreateListOptions, token: Arc<AtomicBool>) -> Result<DeviceList, Error> {
+    if token.load(Ordering::Relaxed) {
+        return Err(Error::UserCancel);
+    }
+
+    let rx = spawn_create_list(options);
+    loop {
+        match rx.recv_timeout(CANCEL_POLL_INTERVAL) {
+            Ok(result) => return result,
+            Err(RecvTimeoutError::Timeout) => {
+                if token.load(Ordering::Relaxed) {
+                    warn!("Device enumeration cancelled");
+                    return Err(Error::UserCancel);
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => return Err(Error::Other),
+        }
+    }
+}
+
+/// Enumerates connected devices as [`create_list`], giving up after
+/// `timeout`.
+///
+/// On some systems, for example with flaky USB hubs, enumeration can hang.
+/// This runs it on a worker thread, and returns [`Error::Timeout`] if it
+/// hasn't completed within `timeout`, so the caller isn't blocked
+/// indefinitely.
+///
+/// libwdi's enumeration can't itself be interrupted, so after a timeout the
+/// worker thread may outlive this call, running until the underlying call
+/// completes, if ever.  Its result is then discarded.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use wdi_rs::{create_list_with_timeout, CreateListOptions, Error};
+///
+/// match create_list_with_timeout(CreateListOptions::all(), Duration::from_secs(10)) {
+///     Ok(devices) => println!("Found {} devices", devices.len()),
+///     Err(Error::Timeout) => println!("Enumeration is hanging - check USB hubs"),
+///     Err(e) => return Err(e),
+/// }
+/// # Ok::<(), wdi_rs::Error>(())
+/// ```
+pub fn create_list_with_timeout(options: CreateListOptions, timeout: Duration) -> Result<DeviceList, Error> {
+    match spawn_create_list(options).recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => {
+            warn!("Device enumeration timed out after {:?}", timeout);
+            Err(Error::Timeout)
+        }
+        Err(RecvTimeoutError::Disconnected) => Err(Error::Other),
+    }
+}
+
+/// Run [`create_list`] on a worker thread, returning a receiver for its
+/// result.
+fn spawn_create_list(options: CreateListOptions) -> Receiver<Result<DeviceList, Error>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        // Fails if the caller gave up, in which case the list is freed here
+        let _ = tx.send(create_list(options));
+    });
+    rx
+}
+
+/// Enumerates connected devices, calling `f` for each one
+///
+/// Unlike [`create_list`] followed by iteration, a single [`Device`] is reused
+/// for every call, avoiding allocating each device's strings afresh.  This
+/// suits tools which enumerate at high frequency, for example while waiting
+/// for a device to appear.  The libwdi list is freed before returning.
+/// 
+/// # Arguments
+/// * `options` - The options to use when creating the device list.
+/// * `f` - Called with each device in turn, in libwdi's enumeration order.
+pub fn create_list_for_each<F: FnMut(&Device)>(options: CreateListOptions, mut f: F) -> Result<(), Error> {
+    let list = create_list(options)?;
+
+    let mut device = Device::default();
+
+    let mut current = list.head;
+    while !current.is_null() {
+        unsafe {
+            device.update_from_raw(current);
+            current = (*current).next;
+        }
+        f(&device);
+    }
+
+    Ok(())
+}
+
+/// Enumerates connected devices and returns those currently using the WinUSB
+/// driver
+///
+/// These are the devices a user-mode application can open directly, for
+/// example with [nusb](https://crates.io/crates/nusb) or
+/// [rusb](https://crates.io/crates/rusb).
+pub fn list_winusb_devices() -> Result<Vec<Device>, Error> {
+    let devices = create_list(CreateListOptions::all())?;
+
+    Ok(devices.iter()
+        .filter(|d| d.driver_kind() == DriverKind::WinUsb)
+        .collect())
+}
+
+/// Enumerates connected devices and returns them as a JSON array, for
+/// scripts and other tooling.
+///
+/// Each device is serialized as a JSON object with the same fields as
+/// [`Device`], such as `vid`, `pid`, `desc`, `driver` and `device_id`.  If
+/// no devices are found, this returns an empty array rather than
+/// `Error::NoDevice`.
+///
+/// # Errors
+/// * Returns any error from enumerating the devices, other than
+///   `Error::NoDevice`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use wdi_rs::{list_devices_json, CreateListOptions};
+///
+/// println!("{}", list_devices_json(CreateListOptions::all())?);
+/// # Ok::<(), wdi_rs::Error>(())
+/// ```
+#[cfg(feature = "serde")]
+pub fn list_devices_json(options: CreateListOptions) -> Result<String, Error> {
+    let devices = match create_list(options) {
+        Ok(devices) => devices.into_vec(),
+        Err(Error::NoDevice) => Vec::new(),
+        Err(e) => return Err(e),
+    };
+
+    serde_json::to_string(&devices).map_err(|e| {
+        error!("Failed to serialize device list: {}", e);
+        Error::Resource
+    })
+}
+
+/// Looks up the vendor name for a USB Vendor ID in libwdi's database
+pub(crate) fn get_vendor_name(vid: u16) -> Option<String> {
+    unsafe {
+        let name = wdi_get_vendor_name(vid);
+        if name.is_null() {
+            None
+        } else {
+            CStr::from_ptr(name).to_str().ok().map(|s| s.to_owned())
+        }
     }
+}
+
+/// Checks whether this build of libwdi supports installing the given driver
+/// type - that is, whether the driver's files are embedded in it.
+///
+/// libwdi is typically built with only some drivers embedded, in which case
+/// preparing a driver of another type fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// use wdi_rs::{is_driver_supported, DriverType};
+///
+/// if !is_driver_supported(DriverType::LibUsbK) {
+///     println!("libusbK isn't available, falling back to WinUSB");
+/// }
+/// ```
+pub fn is_driver_supported(driver_type: DriverType) -> bool {
+    unsafe { wdi_is_driver_supported(driver_type.to_c_int(), ptr::null_mut()) != 0 }
+}
+
+/// Checks whether a file is embedded in this build of libwdi, and so will be
+/// extracted to the driver directory by [`prepare_driver`].
+///
+/// This helps diagnose driver packages missing a file, such as a WDF
+/// coinstaller, which otherwise surface as opaque installation failures.
+///
+/// # Arguments
+/// * `path` - The relative path the file is extracted to within the driver
+///   directory, e.g. `amd64`.  If empty, the file matches wherever it would
+///   be extracted.
+/// * `name` - The file's name, e.g. `WdfCoInstaller01011.dll`.
+///
+/// # Errors
+/// * Returns `Error::InvalidParam` if either argument contains a NUL
+///   character.
+///
+/// # Examples
+///
+/// ```no_run
+/// use wdi_rs::is_file_embedded;
+///
+/// if !is_file_embedded("amd64", "libusbK.sys")? {
+///     println!("libusbK driver missing for amd64");
+/// }
+/// # Ok::<(), wdi_rs::Error>(())
+/// ```
+pub fn is_file_embedded(path: &str, name: &str) -> Result<bool, Error> {
+    let path_c = if path.is_empty() {
+        None
+    } else {
+        Some(CString::new(path).map_err(|_| Error::InvalidParam)?)
+    };
+    let name_c = CString::new(name).map_err(|_| Error::InvalidParam)?;
+
+    let embedded = unsafe {
+        wdi_is_file_embedded(path_c.as_ref().map_or(ptr::null(), |p| p.as_ptr()), name_c.as_ptr())
+    };
+    Ok(embedded != 0)
+}
 
-    Ok(DeviceList { head: list })
+/// Returns the WDF (KMDF) version used by the drivers embedded in libwdi,
+/// as an integer - e.g. `1011` for version 1.11, matching the suffix of the
+/// WDF coinstaller, `WdfCoInstaller01011.dll`.
+///
+/// This is useful for diagnostics, such as bug reports, and for checking
+/// whether a Windows version supports the driver packages libwdi prepares.
+pub fn wdf_version() -> i32 {
+    unsafe { wdi_get_wdf_version() }
 }
 
 /// Options for preparing a driver, as exposed by libwdi
@@ -354,6 +1700,51 @@ impl Default for PrepareDriverOptions {
     }
 }
 
+/// Convert an optional string for passing to libwdi, which takes a null
+/// pointer for an absent string.
+///
+/// Returns `Error::InvalidParam` if the string contains a NUL byte, rather
+/// than passing libwdi a null pointer in its place, or if it is longer than
+/// libwdi's `WDI_MAX_STRLEN` supports.  Used for identifiers, which would
+/// match the wrong device, or no device, if truncated.
+fn optional_cstring(field: &str, value: &Option<String>) -> Result<Option<CString>, Error> {
+    if let Some(s) = value.as_deref().filter(|s| s.len() >= WDI_MAX_STRLEN) {
+        warn!("Invalid {} - {} bytes, longer than libwdi supports: {:?}", field, s.len(), s);
+        return Err(Error::InvalidParam);
+    }
+    optional_display_cstring(field, value)
+}
+
+/// Convert an optional display string, such as a description, for passing
+/// to libwdi.
+///
+/// As [`optional_cstring`], except that strings longer than libwdi's
+/// `WDI_MAX_STRLEN` supports are truncated, at a character boundary, with a
+/// warning.
+fn optional_display_cstring(field: &str, value: &Option<String>) -> Result<Option<CString>, Error> {
+    value.as_deref()
+        .map(|s| CString::new(truncate_to_max_strlen(field, s)))
+        .transpose()
+        .map_err(|_| {
+            warn!("Invalid {} - contains a NUL byte: {:?}", field, value);
+            Error::InvalidParam
+        })
+}
+
+/// Truncate a string so it fits, with its NUL, in `WDI_MAX_STRLEN` bytes.
+fn truncate_to_max_strlen<'a>(field: &str, s: &'a str) -> &'a str {
+    if s.len() < WDI_MAX_STRLEN {
+        return s;
+    }
+
+    let mut len = WDI_MAX_STRLEN - 1;
+    while !s.is_char_boundary(len) {
+        len -= 1;
+    }
+    warn!("{} is {} bytes, longer than libwdi supports - truncating to {} bytes: {:?}", field, s.len(), len, s);
+    &s[..len]
+}
+
 /// Prepares a driver for installation using libwdi
 /// 
 /// # Arguments
@@ -363,6 +1754,8 @@ impl Default for PrepareDriverOptions {
 /// * `options` - The options to use when preparing the driver.
 /// 
 /// # Errors
+/// * Returns `Error::InvalidParam` if any of the device's or options'
+///   strings contain a NUL byte.
 /// * Returns an `Error` if the preparation fails.
 pub fn prepare_driver(
     device: &Device,
@@ -374,25 +1767,16 @@ pub fn prepare_driver(
     let inf_name_c = CString::new(inf_name).map_err(|_| Error::InvalidParam)?;
     
     // Convert device strings to CString - keep them alive for the C call
-    let desc_c = device.desc.as_ref()
-        .and_then(|s| CString::new(s.as_str()).ok());
-    let driver_c = device.driver.as_ref()
-        .and_then(|s| CString::new(s.as_str()).ok());
-    let device_id_c = device.device_id.as_ref()
-        .and_then(|s| CString::new(s.as_str()).ok());
-    let hardware_id_c = device.hardware_id.as_ref()
-        .and_then(|s| CString::new(s.as_str()).ok());
-    let compatible_id_c = device.compatible_id.as_ref()
-        .and_then(|s| CString::new(s.as_str()).ok());
-    let upper_filter_c = device.upper_filter.as_ref()
-        .and_then(|s| CString::new(s.as_str()).ok());
+    let desc_c = optional_display_cstring("desc", &device.desc)?;
+    let driver_c = optional_cstring("driver", &device.driver)?;
+    let device_id_c = optional_cstring("device_id", &device.device_id)?;
+    let hardware_id_c = optional_cstring("hardware_id", &device.hardware_id)?;
+    let compatible_id_c = optional_cstring("compatible_id", &device.compatible_id)?;
+    let upper_filter_c = optional_cstring("upper_filter", &device.upper_filter)?;
     
-    let vendor_name_c = options.vendor_name.as_ref()
-        .and_then(|s| CString::new(s.as_str()).ok());
-    let device_guid_c = options.device_guid.as_ref()
-        .and_then(|s| CString::new(s.as_str()).ok());
-    let cert_subject_c = options.cert_subject.as_ref()
-        .and_then(|s| CString::new(s.as_str()).ok());
+    let vendor_name_c = optional_display_cstring("vendor_name", &options.vendor_name)?;
+    let device_guid_c = optional_cstring("device_guid", &options.device_guid)?;
+    let cert_subject_c = optional_cstring("cert_subject", &options.cert_subject)?;
 
     let mut device_info = WdiDeviceInfo {
         next: ptr::null_mut(),
@@ -433,6 +1817,32 @@ pub fn prepare_driver(
     Ok(())
 }
 
+/// A window handle (`HWND`), used to parent the dialogs libwdi raises - see
+/// [`InstallDriverOptions::with_hwnd`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowHandle(*mut c_void);
+
+// SAFETY: A window handle is a process-wide identifier rather than a pointer
+// to memory, and Windows allows a window on another thread to own a dialog.
+unsafe impl Send for WindowHandle {}
+unsafe impl Sync for WindowHandle {}
+
+impl WindowHandle {
+    /// Wraps a raw window handle.
+    ///
+    /// # Safety
+    /// `hwnd` must be a valid window handle, and the window must not be
+    /// destroyed while any installation using it is in progress.
+    pub unsafe fn from_raw(hwnd: *mut c_void) -> Self {
+        WindowHandle(hwnd)
+    }
+
+    /// Returns the raw window handle.
+    pub fn as_raw(&self) -> *mut c_void {
+        self.0
+    }
+}
+
 /// Options for installing a driver, as exposed by libwdi
 /// 
 /// You can use `default()` to construct.
@@ -442,11 +1852,37 @@ pub struct InstallDriverOptions {
     /// Timeout in milliseconds to wait for pending installations.
     /// Driver installation often takes around a minute to complete.
     pub pending_install_timeout: u32,
+    /// The window to parent any dialogs libwdi raises to, so they are modal
+    /// to it rather than possibly appearing behind it.  Defaults to `None`.
+    pub hwnd: Option<WindowHandle>,
 }
 
 impl InstallDriverOptions {
     /// The default timeout for pending installations in milliseconds
     pub const DEFAULT_PENDING_INSTALL_TIMEOUT: u32 = 120000;
+
+    /// Sets the window to parent any dialogs libwdi raises to, typically a
+    /// GUI application's main window.
+    ///
+    /// # Safety
+    /// `hwnd` must be a valid window handle, and the window must not be
+    /// destroyed while any installation using these options is in progress.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use wdi_rs::{DriverInstaller, InstallDriverOptions};
+    ///
+    /// # let main_window: *mut std::ffi::c_void = std::ptr::null_mut();
+    /// // SAFETY: The main window outlives the installation
+    /// let opts = unsafe { InstallDriverOptions::default().with_hwnd(main_window) };
+    /// let installer = DriverInstaller::for_device(0x1234, 0x5678)
+    ///     .with_install_options(opts);
+    /// ```
+    pub unsafe fn with_hwnd(mut self, hwnd: *mut c_void) -> Self {
+        self.hwnd = Some(unsafe { WindowHandle::from_raw(hwnd) });
+        self
+    }
 }
 
 impl Default for InstallDriverOptions {
@@ -454,7 +1890,50 @@ impl Default for InstallDriverOptions {
         InstallDriverOptions {
             install_filter_driver: false,
             pending_install_timeout: Self::DEFAULT_PENDING_INSTALL_TIMEOUT,
+            hwnd: None,
+        }
+    }
+}
+
+/// How concurrent driver installations are handled
+///
+/// libwdi is not designed for concurrent installations, so [`install_driver`]
+/// only allows one at a time across the process.  Set the policy with
+/// [`set_concurrency_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConcurrencyPolicy {
+    /// Block until any in-progress installation completes.  This is the
+    /// default.
+    #[default]
+    Serialize,
+
+    /// Return `Error::Busy` immediately if another installation is in
+    /// progress.  Useful for callers that never expect to overlap, to surface
+    /// accidental concurrent installs as errors.
+    Reject,
+}
+
+static INSTALL_LOCK: Mutex<()> = Mutex::new(());
+static REJECT_CONCURRENT: AtomicBool = AtomicBool::new(false);
+
+/// Sets how concurrent driver installations are handled, process-wide.
+pub fn set_concurrency_policy(policy: ConcurrencyPolicy) {
+    REJECT_CONCURRENT.store(policy == ConcurrencyPolicy::Reject, Ordering::Relaxed);
+}
+
+/// Takes the installation lock, according to the concurrency policy.
+fn lock_install() -> Result<MutexGuard<'static, ()>, Error> {
+    if REJECT_CONCURRENT.load(Ordering::Relaxed) {
+        match INSTALL_LOCK.try_lock() {
+            Ok(guard) => Ok(guard),
+            Err(TryLockError::Poisoned(e)) => Ok(e.into_inner()),
+            Err(TryLockError::WouldBlock) => {
+                warn!("Another driver installation is in progress - rejecting");
+                Err(Error::Busy)
+            }
         }
+    } else {
+        Ok(INSTALL_LOCK.lock().unwrap_or_else(|e| e.into_inner()))
     }
 }
 
@@ -469,7 +1948,13 @@ impl Default for InstallDriverOptions {
 /// * `inf_name` - The name of the INF file to use for installation.
 /// * `options` - The options to use when installing the driver.
 /// 
+/// Only one installation runs at a time - see [`ConcurrencyPolicy`].
+/// 
 /// # Errors
+/// * Returns `Error::Busy` if another installation is in progress and the
+///   policy is [`ConcurrencyPolicy::Reject`].
+/// * Returns `Error::InvalidParam` if any of the device's strings contain a
+///   NUL byte.
 /// * Returns an `Error` if the installation fails.
 pub fn install_driver(
     device: &Device,
@@ -481,18 +1966,12 @@ pub fn install_driver(
     let inf_name_c = CString::new(inf_name).map_err(|_| Error::InvalidParam)?;
 
     // Convert device strings to CString
-    let desc_c = device.desc.as_ref()
-        .and_then(|s| CString::new(s.as_str()).ok());
-    let driver_c = device.driver.as_ref()
-        .and_then(|s| CString::new(s.as_str()).ok());
-    let device_id_c = device.device_id.as_ref()
-        .and_then(|s| CString::new(s.as_str()).ok());
-    let hardware_id_c = device.hardware_id.as_ref()
-        .and_then(|s| CString::new(s.as_str()).ok());
-    let compatible_id_c = device.compatible_id.as_ref()
-        .and_then(|s| CString::new(s.as_str()).ok());
-    let upper_filter_c = device.upper_filter.as_ref()
-        .and_then(|s| CString::new(s.as_str()).ok());
+    let desc_c = optional_display_cstring("desc", &device.desc)?;
+    let driver_c = optional_cstring("driver", &device.driver)?;
+    let device_id_c = optional_cstring("device_id", &device.device_id)?;
+    let hardware_id_c = optional_cstring("hardware_id", &device.hardware_id)?;
+    let compatible_id_c = optional_cstring("compatible_id", &device.compatible_id)?;
+    let upper_filter_c = optional_cstring("upper_filter", &device.upper_filter)?;
 
     let mut device_info = WdiDeviceInfo {
         next: ptr::null_mut(),
@@ -510,11 +1989,12 @@ pub fn install_driver(
     };
 
     let mut opts = WdiOptionsInstallDriver {
-        hwnd: ptr::null_mut(),
+        hwnd: options.hwnd.map_or(ptr::null_mut(), |hwnd| hwnd.as_raw()),
         install_filter_driver: options.install_filter_driver as c_int,
         pending_install_timeout: options.pending_install_timeout,
     };
 
+    let _guard = lock_install()?;
     unsafe {
         let result = wdi_install_driver(
             &mut device_info,
@@ -528,10 +2008,334 @@ pub fn install_driver(
     Ok(())
 }
 
+/// Installs a code signing certificate embedded in libwdi into the Trusted
+/// Publisher store, so drivers signed with it, such as test-signed drivers,
+/// install without warnings.
+///
+/// Requires administrator privileges.
+///
+/// # Arguments
+/// * `cert_name` - The name of the certificate, as embedded in libwdi.
+/// * `disable_warning` - Whether to skip the warning dialog Windows shows
+///   before installing the certificate.
+///
+/// # Errors
+/// * Returns `Error::InvalidParam` if `cert_name` contains a NUL character.
+/// * Returns `Error::NotFound` if no such certificate is embedded.
+/// * Returns an `Error` if the installation fails, e.g. `Error::NeedsAdmin`.
+pub fn install_trusted_certificate(cert_name: &str, disable_warning: bool) -> Result<(), Error> {
+    let cert_name_c = CString::new(cert_name).map_err(|_| Error::InvalidParam)?;
+
+    let mut opts = WdiOptionsInstallCert {
+        hwnd: ptr::null_mut(),
+        disable_warning: disable_warning as c_int,
+    };
+
+    unsafe {
+        let result = wdi_install_trusted_certificate(cert_name_c.as_ptr(), &mut opts);
+        Error::from_code(result)
+    }
+}
+
 /// Sets the log level for libwdi logging.
 pub fn set_log_level(level: LogLevel) -> Result<(), Error> {
     unsafe {
         let result = wdi_set_log_level(level.into());
         Error::from_code(result)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_driver_version() {
+        let version = DriverVersion::from(0x0006_0001_1DB0_4001);
+        assert_eq!(version, DriverVersion { major: 6, minor: 1, build: 7600, revision: 16385 });
+        assert_eq!(version.to_string(), "6.1.7600.16385");
+        assert_eq!(u64::from(version), 0x0006_0001_1DB0_4001);
+
+        assert!(DriverVersion::from(0x0006_0002_0000_0000) > version);
+        assert!(DriverVersion::from(0x0005_FFFF_FFFF_FFFF) < version);
+    }
+
+    #[test]
+    fn test_thread_safety() {
+        fn assert_send<T: Send>() {}
+        fn assert_sync<T: Sync>() {}
+        assert_send::<DeviceList>();
+        assert_send::<Device>();
+        assert_sync::<Device>();
+    }
+
+    #[test]
+    fn test_device_interface_guid_round_trip() {
+        let guid: DeviceInterfaceGuid = "{6e45736a-2b1b-4078-b772-b3af2b6fde1c}".parse().unwrap();
+        assert_eq!(guid.to_string(), "{6E45736A-2B1B-4078-B772-B3AF2B6FDE1C}");
+        assert_eq!(guid.to_ffi().data4, [0xb7, 0x72, 0xb3, 0xaf, 0x2b, 0x6f, 0xde, 0x1c]);
+
+        let unbraced: DeviceInterfaceGuid = "6E45736A-2B1B-4078-B772-B3AF2B6FDE1C".parse().unwrap();
+        assert_eq!(guid, unbraced);
+        assert_eq!(DeviceInterfaceGuid::from_ffi(guid.to_ffi()), guid);
+
+        let null: DeviceInterfaceGuid = Device::NULL_CONTAINER_ID.parse().unwrap();
+        assert_eq!(null.to_string(), Device::NULL_CONTAINER_ID);
+
+        assert!("6e45736a-2b1b-4078-b772".parse::<DeviceInterfaceGuid>().is_err());
+        assert!("{6e45736a-2b1b-4078-b772-b3af2b6fde1g}".parse::<DeviceInterfaceGuid>().is_err());
+        assert!("{+e45736a-2b1b-4078-b772-b3af2b6fde1c}".parse::<DeviceInterfaceGuid>().is_err());
+    }
+
+    #[test]
+    fn test_error_code_round_trip() {
+        for error in Error::KNOWN {
+            let code = error.to_code();
+            assert_eq!(Error::from_code(code).unwrap_err().to_code(), code);
+        }
+        assert!(Error::from_code(0).is_ok());
+        assert!(matches!(Error::from_code(-42), Err(Error::Unknown(-42))));
+
+        assert!(matches!(Error::from_raw_code(Error::Timeout.code()), Error::Timeout));
+        assert_eq!(Error::from_raw_code(-42).code(), -42);
+        assert!(matches!(Error::from_raw_code(0), Error::Unknown(0)));
+    }
+
+    #[test]
+    fn test_driver_type_round_trip() {
+        for driver_type in DriverType::ALL {
+            assert_eq!(DriverType::from_c_int(driver_type.to_c_int()), Some(driver_type));
+            assert_eq!(DriverType::try_from(driver_type.to_c_int()).ok(), Some(driver_type));
+        }
+        assert_eq!(DriverType::from_c_int(5), None);
+        assert!(matches!(DriverType::try_from(-1), Err(Error::InvalidParam)));
+    }
+
+    #[test]
+    fn test_create_list_error_takes_no_ownership() {
+        // A failed enumeration must never construct a list from the pointer,
+        // which may dangle, so dropping the result can't double free
+        let dangling = ptr::NonNull::<WdiDeviceInfo>::dangling().as_ptr();
+        let result = unsafe { DeviceList::from_create_result(Error::Resource.to_code(), dangling) };
+        assert!(matches!(result, Err(Error::Resource)));
+
+        // A successful but empty enumeration is an empty list, which frees
+        // nothing on drop
+        let list = unsafe { DeviceList::from_create_result(0, ptr::null_mut()) }.unwrap();
+        assert!(list.is_empty());
+        assert_eq!(list.len(), 0);
+        assert!(list.into_vec().is_empty());
+    }
+
+    #[test]
+    fn test_create_list_options_builder() {
+        let opts = CreateListOptions::all().with_hubs(true).trim(false);
+        assert!(opts.list_all && opts.list_hubs && !opts.trim_whitespaces);
+
+        let opts = CreateListOptions::all();
+        assert!(opts.list_all && !opts.list_hubs && opts.trim_whitespaces);
+    }
+
+    #[test]
+    fn test_optional_cstring() {
+        assert!(optional_cstring("desc", &None).unwrap().is_none());
+        assert_eq!(
+            optional_cstring("desc", &Some("Widget".to_string())).unwrap().unwrap().as_bytes(),
+            b"Widget",
+        );
+        assert!(matches!(optional_cstring("desc", &Some("Wid\0get".to_string())), Err(Error::InvalidParam)));
+
+        let fits = "x".repeat(WDI_MAX_STRLEN - 1);
+        assert_eq!(optional_cstring("device_id", &Some(fits.clone())).unwrap().unwrap().as_bytes().len(), WDI_MAX_STRLEN - 1);
+        let long = "x".repeat(WDI_MAX_STRLEN - 2) + "é";
+        assert!(matches!(optional_cstring("device_id", &Some(long.clone())), Err(Error::InvalidParam)));
+
+        let truncated = optional_display_cstring("desc", &Some(long)).unwrap().unwrap();
+        assert_eq!(truncated.as_bytes().len(), WDI_MAX_STRLEN - 2);
+        assert_eq!(optional_display_cstring("desc", &Some(fits)).unwrap().unwrap().as_bytes().len(), WDI_MAX_STRLEN - 1);
+        assert!(matches!(optional_display_cstring("desc", &Some("Wid\0get".to_string())), Err(Error::InvalidParam)));
+    }
+
+    #[test]
+    fn test_com_port_from_friendly_name() {
+        assert_eq!(com_port_from_friendly_name("USB Serial Device (COM3)").as_deref(), Some("COM3"));
+        assert_eq!(com_port_from_friendly_name("Widget (Rev 2) (COM12)").as_deref(), Some("COM12"));
+        assert_eq!(com_port_from_friendly_name("USB Serial Device"), None);
+        assert_eq!(com_port_from_friendly_name("USB Serial Device (COM3"), None);
+    }
+
+    #[test]
+    fn test_empty_description() {
+        let mut device = Device { desc: Some(String::new()), ..test_device(0x1234, 0x5678) };
+        assert_eq!(device.effective_desc(), None);
+        assert_eq!(device.to_string(), "1234:5678 (no description)");
+
+        device.desc = None;
+        assert_eq!(device.to_string(), "1234:5678 (no description)");
+
+        device.desc = Some("ACME Widget".to_string());
+        assert_eq!(device.effective_desc(), Some("ACME Widget"));
+        assert_eq!(device.to_string(), "1234:5678 ACME Widget");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_device_serde_round_trip() {
+        let device = Device {
+            is_composite: true,
+            mi: 1,
+            desc: Some("ACME Widget".to_string()),
+            driver: Some("WinUSB".to_string()),
+            device_id: Some("USB\\VID_1234&PID_5678&MI_01\\6&1A2B3C4D&0&0001".to_string()),
+            driver_version: 0x0006_0002_0000_0000,
+            ..test_device(0x1234, 0x5678)
+        };
+
+        let json = serde_json::to_string(&device).unwrap();
+        let parsed: Device = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.to_string(), device.to_string());
+        assert_eq!(parsed.device_id, device.device_id);
+        assert_eq!(parsed.mi, device.mi);
+        assert_eq!(parsed.driver_version, device.driver_version);
+    }
+
+    #[test]
+    fn test_device_key() {
+        use std::collections::HashMap;
+
+        let device = Device {
+            is_composite: true,
+            mi: 1,
+            device_id: Some("USB\\VID_1234&PID_5678&MI_01\\6&1234&0&0001".to_string()),
+            ..test_device(0x1234, 0x5678)
+        };
+        let lowercase = Device {
+            device_id: device.device_id.as_deref().map(str::to_ascii_lowercase),
+            driver: Some("WinUSB".to_string()),
+            ..device.clone()
+        };
+        let no_id = Device { device_id: None, ..device.clone() };
+        let other_mi = Device { mi: 2, ..no_id.clone() };
+
+        let mut cache = HashMap::new();
+        cache.insert(device.key(), "cached");
+        assert_eq!(cache.get(&lowercase.key()), Some(&"cached"));
+        assert_eq!(no_id.key(), DeviceKey(DeviceKeyId::Usb { vid: 0x1234, pid: 0x5678, mi: Some(1) }));
+        assert_ne!(no_id.key(), other_mi.key());
+        assert_ne!(no_id.key(), device.key());
+
+        // Functions in one container are distinguished by VID and PID
+        let container = Some("{01234567-89AB-CDEF-0123-456789ABCDEF}".to_string());
+        let hub = test_device(0x1234, 0x0001);
+        let built_in = test_device(0x1234, 0x0002);
+        assert_ne!(hub.key_in_container(container.clone()), built_in.key_in_container(container.clone()));
+        assert_eq!(
+            hub.key_in_container(container.clone()),
+            Device { device_id: Some("USB\\VID_1234&PID_0001\\1".to_string()), ..hub.clone() }
+                .key_in_container(container.map(|c| c.to_ascii_lowercase())),
+        );
+    }
+
+    #[test]
+    fn test_device_identity() {
+        use std::collections::HashSet;
+
+        let device = Device {
+            desc: Some("ACME Widget".to_string()),
+            device_id: Some("USB\\VID_1234&PID_5678\\ABC123".to_string()),
+            ..test_device(0x1234, 0x5678)
+        };
+        let reinstalled = Device {
+            driver: Some("WinUSB".to_string()),
+            driver_version: 0x0006_0002_0000_0000,
+            ..device.clone()
+        };
+        let other = Device {
+            device_id: Some("USB\\VID_1234&PID_5678\\DEF456".to_string()),
+            ..device.clone()
+        };
+
+        assert_eq!(device, reinstalled);
+        assert_ne!(device, other);
+
+        let seen: HashSet<_> = [device, reinstalled, other].into_iter().collect();
+        assert_eq!(seen.len(), 2);
+    }
+
+    #[test]
+    fn test_group_by_key() {
+        let interface = |vid: u16, mi: u8, id: &str| Device {
+            is_composite: true,
+            mi,
+            device_id: Some(id.to_string()),
+            ..test_device(vid, 0x5678)
+        };
+        let devices = vec![
+            interface(0x1234, 1, "A1"),
+            interface(0x9999, 0, "B0"),
+            interface(0x1234, 0, "A0"),
+            interface(0x4321, 2, "A2"),
+        ];
+
+        // Group everything except VID 0x9999 by a shared container
+        let groups = group_by_key(devices, |d| (d.vid != 0x9999).then_some("container"));
+        let ids: Vec<Vec<_>> = groups.iter()
+            .map(|g| g.iter().map(|d| d.device_id.as_deref().unwrap()).collect())
+            .collect();
+        assert_eq!(ids, [vec!["A0", "A1", "A2"], vec!["B0"]]);
+    }
+
+    #[test]
+    fn test_generic_driver_not_functional() {
+        let device = test_device(0x1234, 0x5678);
+        assert!(!device.has_functional_driver());
+
+        let generic = Device { driver: Some("usbccgp".to_string()), ..device };
+        assert!(!generic.has_functional_driver());
+    }
+
+    #[test]
+    fn test_composite_detail() {
+        let interface = |mi: u8| Device { is_composite: true, mi, ..test_device(0x1234, 0x5678) };
+        let other = Device { pid: 0x9999, ..interface(0) };
+        let devices = [interface(0), interface(1), test_device(0x1234, 0x5678), other];
+
+        assert_eq!(devices[2].composite_detail(&devices), CompositeDetail::NotComposite);
+        assert_eq!(
+            devices[1].composite_detail(&devices),
+            CompositeDetail::Composite { mi: 1, parent_id: None, interface_count: 2 },
+        );
+    }
+
+    #[test]
+    fn test_serial_from_instance_id() {
+        assert_eq!(serial_from_instance_id("USB\\VID_1234&PID_5678\\ABC123"), Some("ABC123"));
+        assert_eq!(serial_from_instance_id("USB\\VID_1234&PID_5678\\5&2B7E9B3A&0&1"), None);
+        assert_eq!(serial_from_instance_id("HID\\VID_1234&PID_5678\\ABC123"), None);
+    }
+
+    #[test]
+    fn test_revision_from_hardware_id() {
+        assert_eq!(revision_from_hardware_id("USB\\VID_1234&PID_5678&REV_0100"), Some(0x0100));
+        assert_eq!(revision_from_hardware_id("USB\\VID_1234&PID_5678&REV_10AB&MI_01"), Some(0x10ab));
+        assert_eq!(revision_from_hardware_id("USB\\VID_1234&PID_5678"), None);
+    }
+
+    #[test]
+    fn test_driver_replacement_matrix() {
+        assert_eq!(DriverKind::from_driver(None), DriverKind::None);
+        assert_eq!(DriverKind::from_driver(Some("WinUSB")), DriverKind::WinUsb);
+        assert_eq!(DriverKind::from_driver(Some("winusb")), DriverKind::WinUsb);
+        assert_eq!(DriverKind::from_driver(Some("LIBUSB0")), DriverKind::LibUsb0);
+        assert_eq!(DriverKind::from_driver(Some("WinUs")), DriverKind::Other("WinUs".to_string()));
+        assert_eq!(DriverKind::from_driver(Some("usbser")), DriverKind::Cdc);
+        assert_eq!(DriverKind::from_driver(Some("HidUsb")), DriverKind::Other("HidUsb".to_string()));
+
+        assert!(DriverType::WinUsb.is_compatible_replacement_for(&DriverKind::None));
+        assert!(DriverType::User.is_compatible_replacement_for(&DriverKind::None));
+        assert!(DriverType::LibUsb0.is_compatible_replacement_for(&DriverKind::WinUsb));
+        assert!(DriverType::WinUsb.is_compatible_replacement_for(&DriverKind::Cdc));
+        assert!(!DriverType::User.is_compatible_replacement_for(&DriverKind::WinUsb));
+        assert!(!DriverType::WinUsb.is_compatible_replacement_for(&DriverKind::Other("HidUsb".to_string())));
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Signature verification of prepared driver packages, via WinTrust.
+
+use crate::ffi::{WintrustData, WintrustFileInfo, WinVerifyTrust, WINTRUST_ACTION_GENERIC_VERIFY_V2};
+use crate::ffi::{WTD_UI_NONE, WTD_REVOKE_NONE, WTD_CHOICE_FILE, WTD_STATEACTION_VERIFY, WTD_STATEACTION_CLOSE};
+use crate::ffi::{TRUST_E_NOSIGNATURE, TRUST_E_SUBJECT_FORM_UNKNOWN, TRUST_E_PROVIDER_UNKNOWN};
+use crate::ring_log::debug;
+use crate::setupapi::to_wide;
+use std::mem;
+use std::path::Path;
+use std::ptr;
+
+/// The result of verifying a driver catalog's signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureStatus {
+    /// The catalog is signed by a trusted publisher
+    Trusted,
+    /// The catalog is signed, but Windows doesn't trust the signature, with
+    /// the WinTrust error code.  This is expected for catalogs self-signed
+    /// by libwdi, as the signing certificate is only added to the trusted
+    /// stores when the driver is installed.
+    Untrusted(i32),
+    /// The catalog isn't signed
+    Unsigned,
+    /// No catalog was produced, so there was nothing to verify
+    NotChecked,
+}
+
+/// Verify the signature of the given catalog (or other signed) file.
+pub(crate) fn verify_signature(path: &Path) -> SignatureStatus {
+    let Some(path_str) = path.to_str() else {
+        debug!("Unable to verify non-UTF-8 path: {}", path.display());
+        return SignatureStatus::NotChecked;
+    };
+    let path_w = to_wide(path_str);
+
+    let mut file_info = WintrustFileInfo {
+        cb_struct: mem::size_of::<WintrustFileInfo>() as u32,
+        file_path: path_w.as_ptr(),
+        file: ptr::null_mut(),
+        known_subject: ptr::null(),
+    };
+    let mut data = WintrustData {
+        cb_struct: mem::size_of::<WintrustData>() as u32,
+        policy_callback_data: ptr::null_mut(),
+        sip_client_data: ptr::null_mut(),
+        ui_choice: WTD_UI_NONE,
+        revocation_checks: WTD_REVOKE_NONE,
+        union_choice: WTD_CHOICE_FILE,
+        file: &mut file_info,
+        state_action: WTD_STATEACTION_VERIFY,
+        state_data: ptr::null_mut(),
+        url_reference: ptr::null_mut(),
+        prov_flags: 0,
+        ui_context: 0,
+        signature_settings: ptr::null_mut(),
+    };
+
+    let result = unsafe {
+        WinVerifyTrust(ptr::null_mut(), &WINTRUST_ACTION_GENERIC_VERIFY_V2, &mut data)
+    };
+
+    // Release the state data allocated by the verify action
+    data.state_action = WTD_STATEACTION_CLOSE;
+    unsafe { WinVerifyTrust(ptr::null_mut(), &WINTRUST_ACTION_GENERIC_VERIFY_V2, &mut data) };
+
+    debug!("WinVerifyTrust({}) returned {:#010x}", path.display(), result);
+    match result {
+        0 => SignatureStatus::Trusted,
+        TRUST_E_NOSIGNATURE | TRUST_E_SUBJECT_FORM_UNKNOWN | TRUST_E_PROVIDER_UNKNOWN => {
+            SignatureStatus::Unsigned
+        }
+        code => SignatureStatus::Untrusted(code),
+    }
+}